@@ -15,39 +15,84 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::env;
-use std::io::Read;
+use std::io::{self, Read};
 use std::fs::File;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::error::Error;
 
-pub fn get_source_text() -> String {
+use parser::AstFormat;
+use scanner::Dialect;
+
+/// Opens the source file given as the first argument for reading.
+/// A path of "-" reads the program from the standard input instead.
+pub fn open_source() -> Box<dyn Read + Send> {
     let path = get_path();
-    let display = path.display();
+    if path == Path::new("-") {
+        return Box::new(io::stdin());
+    }
+    match File::open(&path) {
+        Err(why) => panic!(
+            "couldn't open {}: {}",
+            path.display(),
+            Error::description(&why)
+        ),
+        Ok(file) => Box::new(file),
+    }
+}
 
-    let mut file = match File::open(&path) {
-        Err(why) => panic!("couldn't open {}: {}", display, Error::description(&why)),
-        Ok(file) => file,
-    };
+pub fn get_source_text() -> String {
     let mut source_text = String::new();
-    // I should read the file to a string until a ';' character comes up and then start a new string
-    // and store all the resulting strings in a Vec or some other data structure. I don't know how
-    // to do that in rust yet and that's why I just read the whole source file into a string and
-    // chop up the string into smaller strings afterwards.
-    match file.read_to_string(&mut source_text) {
-        Err(why) => panic!("couldn't read {}: {}", display, Error::description(&why)),
+    match open_source().read_to_string(&mut source_text) {
+        Err(why) => panic!(
+            "couldn't read {}: {}",
+            get_path().display(),
+            Error::description(&why)
+        ),
         Ok(_) => {}
     }
     source_text
 }
 
+/// Reads the dialect from a "--dialect=<name>" argument. Standard Mini-PL is used by default.
+pub fn get_dialect() -> Dialect {
+    for argument in env::args().skip(1) {
+        if let Some(dialect) = argument.strip_prefix("--dialect=") {
+            return dialect.parse().unwrap_or_else(|e| panic!("{}", e));
+        }
+    }
+    Dialect::Standard
+}
+
+/// Reads the syntax tree format from a "--<option>=<format>" argument, e.g. "--emit=json".
+pub fn get_ast_format(option: &str) -> Option<AstFormat> {
+    let prefix = format!("--{}=", option);
+    env::args()
+        .skip(1)
+        .find(|argument| argument.starts_with(&prefix))
+        .map(|argument| {
+            argument[prefix.len()..]
+                .parse()
+                .unwrap_or_else(|e| panic!("{}", e))
+        })
+}
+
+/// Whether the first argument that isn't an option is the "fmt" command, which formats the
+/// source instead of running it.
+pub fn is_format_command() -> bool {
+    env::args()
+        .skip(1)
+        .find(|argument| !argument.starts_with("--"))
+        .is_some_and(|argument| argument == "fmt")
+}
+
 fn get_path() -> PathBuf {
     //let f = try!(File::open());
     let mut path = String::new();
 
-    for (i, argument) in env::args().enumerate() {
-        if i == 1 {
-            path = argument.to_string()
-        }
+    // The first argument that isn't an option or the command is the path.
+    let skip = if is_format_command() { 1 } else { 0 };
+    if let Some(argument) = env::args().skip(1).filter(|a| !a.starts_with("--")).nth(skip) {
+        path = argument;
     }
 
     if path.is_empty() {
@@ -0,0 +1,375 @@
+//     mini-pl compiler.
+//     Copyright (C) 2018  Victor Bankowski
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use parser::{self, Expression, ExpressionKind, Operand, OperandKind, ParseError, Statement, StatementKind,
+             UnaryOperator};
+use scanner::{LexError, LexerConfig, Scanner, SpannedToken, Token, TriviaKind};
+use symbol::Symbol;
+
+#[cfg(test)]
+mod test;
+
+/// The text of one indentation level.
+const INDENT: &str = "    ";
+
+/// The reason a source couldn't be formatted.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FormatError {
+    Lex(Vec<LexError>),
+    Parse(Vec<ParseError>),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let errors: Vec<String> = match *self {
+            FormatError::Lex(ref errors) => errors.iter().map(|e| e.to_string()).collect(),
+            FormatError::Parse(ref errors) => errors.iter().map(|e| e.to_string()).collect(),
+        };
+        write!(f, "{}", errors.join("\n"))
+    }
+}
+
+/// Formats a program in the canonical style: one statement per line, the bodies of blocks indented
+/// by four spaces, single spaces around binary operators and only the brackets the precedence of
+/// the operators requires. Comments are kept and at most one blank line is kept between
+/// statements. Formatting the output again doesn't change it and parsing the output gives the
+/// same statements as parsing the source.
+pub fn format(source: &str, config: LexerConfig) -> Result<String, FormatError> {
+    let mut scanner = Scanner::with_config(LexerConfig {
+        preserve_trivia: true,
+        ..config
+    });
+    let mut tokens = Vec::new();
+    scanner.scan(source, &mut tokens);
+    scanner.finish(&mut tokens);
+    if !scanner.errors().is_empty() {
+        return Err(FormatError::Lex(scanner.errors().to_vec()));
+    }
+    let comments = comments(&tokens);
+    let mut statements = Vec::new();
+    let errors = parser::parse(&mut VecDeque::from(tokens), &mut statements);
+    if !errors.is_empty() {
+        return Err(FormatError::Parse(errors));
+    }
+    let mut formatter = Formatter {
+        source,
+        comments,
+        next_comment: 0,
+        output: String::new(),
+        indent: 0,
+        last_end: 0,
+        block_start: true,
+    };
+    formatter.statements(&statements);
+    formatter.comments_before(usize::MAX);
+    Ok(formatter.output)
+}
+
+/// A comment or a doc comment of the source.
+struct Comment {
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+/// Collects the comments from tokens scanned with trivia preserved, in the order of the source.
+fn comments(tokens: &[SpannedToken]) -> Vec<Comment> {
+    let mut comments = Vec::new();
+    for token in tokens {
+        for trivia in &token.leading_trivia {
+            match trivia.kind {
+                TriviaKind::LineComment | TriviaKind::BlockComment => {
+                    comments.push(comment(trivia.span.start, &trivia.text))
+                }
+                TriviaKind::Whitespace | TriviaKind::Skipped => {}
+            }
+        }
+        if let Token::DocComment(_) = token.token {
+            comments.push(comment(token.span.start, &token.text));
+        }
+    }
+    comments
+}
+
+/// Line comments end in the line break, which isn't a part of the comment in the output.
+fn comment(start: usize, text: &str) -> Comment {
+    let text = text.trim_end();
+    Comment {
+        start,
+        end: start + text.len(),
+        text: text.to_string(),
+    }
+}
+
+struct Formatter<'s> {
+    source: &'s str,
+    comments: Vec<Comment>,
+    /// The first comment that hasn't been written yet.
+    next_comment: usize,
+    output: String,
+    /// The number of blocks the current line is in.
+    indent: usize,
+    /// Where the last statement or comment that was written ended in the source.
+    last_end: usize,
+    /// Whether nothing has been written to the current block yet. Blank lines are not kept at the
+    /// start of a block.
+    block_start: bool,
+}
+
+impl<'s> Formatter<'s> {
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        // Comments inside a statement are moved before it. Blocks keep the comments of their
+        // bodies in the bodies.
+        let header_end = match statement.kind {
+            StatementKind::For(_, _, ref to, _) => to.span.end,
+            StatementKind::If(ref condition, _, _) | StatementKind::While(ref condition, _) => condition.span.end,
+            // Comments in the header of a procedure go to its body.
+            StatementKind::Procedure(..) => statement.span.start,
+            _ => statement.span.end,
+        };
+        self.comments_before(header_end);
+        self.start_line(statement.span.start);
+        match statement.kind {
+            StatementKind::Declaration(identifier, ref typ, ref expression, _) => {
+                // The doc comments were written before the declaration with the other comments.
+                self.output.push_str(&format!("var {} : {}", identifier, typ));
+                if let Some(ref expression) = *expression {
+                    self.output.push_str(" := ");
+                    write_expression(&mut self.output, expression);
+                }
+            }
+            StatementKind::Assignment(identifier, ref expression) => {
+                self.output.push_str(&format!("{} := ", identifier));
+                write_expression(&mut self.output, expression);
+            }
+            StatementKind::IndexedAssignment(identifier, ref index, ref expression) => {
+                self.output.push_str(&format!("{}[", identifier));
+                write_expression(&mut self.output, index);
+                self.output.push_str("] := ");
+                write_expression(&mut self.output, expression);
+            }
+            StatementKind::For(identifier, ref from, ref to, ref body) => {
+                self.output.push_str(&format!("for {} in ", identifier));
+                write_expression(&mut self.output, from);
+                self.output.push_str("..");
+                write_expression(&mut self.output, to);
+                self.output.push_str(" do\n");
+                self.last_end = header_end;
+                self.body(body, statement.span.end);
+                self.write_indent();
+                self.output.push_str("end for");
+            }
+            StatementKind::If(ref condition, ref then, ref otherwise) => {
+                self.output.push_str("if ");
+                write_expression(&mut self.output, condition);
+                self.output.push_str(" then\n");
+                self.last_end = header_end;
+                // Comments between the branches go to the else branch.
+                if otherwise.is_empty() {
+                    self.body(then, statement.span.end);
+                } else {
+                    self.body(then, 0);
+                    self.write_indent();
+                    self.output.push_str("else\n");
+                    self.body(otherwise, statement.span.end);
+                }
+                self.write_indent();
+                self.output.push_str("end if");
+            }
+            StatementKind::While(ref condition, ref body) => {
+                self.output.push_str("while ");
+                write_expression(&mut self.output, condition);
+                self.output.push_str(" do\n");
+                self.last_end = header_end;
+                self.body(body, statement.span.end);
+                self.write_indent();
+                self.output.push_str("end while");
+            }
+            StatementKind::Procedure(name, ref parameters, ref return_type, ref body) => {
+                let keyword = if return_type.is_some() { "function" } else { "procedure" };
+                let parameters: Vec<String> = parameters.iter().map(|p| p.to_string()).collect();
+                self.output.push_str(&format!("{} {}({})", keyword, name, parameters.join(", ")));
+                if let Some(ref return_type) = *return_type {
+                    self.output.push_str(&format!(" : {}", return_type));
+                }
+                self.output.push_str(" do\n");
+                self.last_end = header_end;
+                self.body(body, statement.span.end);
+                self.write_indent();
+                self.output.push_str(&format!("end {}", keyword));
+            }
+            StatementKind::Call(name, ref arguments) => write_call(&mut self.output, name, arguments),
+            StatementKind::Return(ref expression) => {
+                self.output.push_str("return");
+                if let Some(ref expression) = *expression {
+                    self.output.push(' ');
+                    write_expression(&mut self.output, expression);
+                }
+            }
+            StatementKind::Read(identifier) => self.output.push_str(&format!("read {}", identifier)),
+            StatementKind::Print(ref expression) => {
+                self.output.push_str("print ");
+                write_expression(&mut self.output, expression);
+            }
+            StatementKind::Assert(ref expression) => {
+                self.output.push_str("assert (");
+                write_expression(&mut self.output, expression);
+                self.output.push(')');
+            }
+        }
+        self.output.push(';');
+        self.last_end = statement.span.end;
+        self.trailing_comment();
+        self.output.push('\n');
+    }
+
+    /// Writes the statements of a block indented by one level followed by the comments in the block
+    /// that start before ´end´.
+    fn body(&mut self, statements: &[Statement], end: usize) {
+        self.indent += 1;
+        self.block_start = true;
+        self.statements(statements);
+        self.comments_before(end);
+        self.indent -= 1;
+        self.block_start = false;
+    }
+
+    /// Writes the comments that start before ´end´ on lines of their own.
+    fn comments_before(&mut self, end: usize) {
+        while self.next_comment < self.comments.len() && self.comments[self.next_comment].start < end {
+            let (start, end) = {
+                let comment = &self.comments[self.next_comment];
+                (comment.start, comment.end)
+            };
+            self.start_line(start);
+            self.output.push_str(&self.comments[self.next_comment].text);
+            self.output.push('\n');
+            self.last_end = end;
+            self.next_comment += 1;
+        }
+    }
+
+    /// Keeps a comment that was on the same line after the statement that just ended there.
+    fn trailing_comment(&mut self) {
+        if let Some(comment) = self.comments.get(self.next_comment) {
+            let between = &self.source[self.last_end..comment.start];
+            if between.chars().all(|c| c == ';' || (c.is_whitespace() && c != '\n')) {
+                self.output.push(' ');
+                self.output.push_str(&comment.text);
+                self.last_end = comment.end;
+                self.next_comment += 1;
+            }
+        }
+    }
+
+    /// Starts the line of a statement or a comment that starts at ´start´ in the source. A blank
+    /// line is kept before it if there were blank lines before it in the source.
+    fn start_line(&mut self, start: usize) {
+        if !self.block_start && start > self.last_end
+            && self.source[self.last_end..start].matches('\n').count() > 1
+        {
+            self.output.push('\n');
+        }
+        self.block_start = false;
+        self.write_indent();
+    }
+
+    fn write_indent(&mut self) {
+        for _ in 0..self.indent {
+            self.output.push_str(INDENT);
+        }
+    }
+}
+
+/// The precedence of unary operators, which bind more tightly than any binary operator.
+const UNARY_PRECEDENCE: u8 = 6;
+/// The precedence of operands that never need brackets.
+const ATOM_PRECEDENCE: u8 = 7;
+
+fn write_expression(output: &mut String, expression: &Expression) {
+    match expression.kind {
+        ExpressionKind::Binary(ref left, ref operator, ref right) => {
+            // Operators are left associative so an operand of the same precedence only needs
+            // brackets on the right side.
+            let precedence = operator.precedence();
+            write_operand(output, left, precedence);
+            output.push_str(&format!(" {} ", operator));
+            write_operand(output, right, precedence + 1);
+        }
+        ExpressionKind::Unary(UnaryOperator::Not, ref operand) => {
+            output.push('!');
+            write_operand(output, operand, UNARY_PRECEDENCE);
+        }
+        ExpressionKind::Singleton(ref operand) => write_operand(output, operand, 0),
+    }
+}
+
+/// Writes the operand in brackets if its precedence is lower than ´min_precedence´.
+fn write_operand(output: &mut String, operand: &Operand, min_precedence: u8) {
+    match operand.kind {
+        OperandKind::Int(ref n) => output.push_str(&n.to_string()),
+        OperandKind::StringLiteral(s) => output.push_str(&Token::StringLiteral(s).to_string()),
+        OperandKind::Bool(b) => output.push_str(&b.to_string()),
+        OperandKind::Identifier(i) => output.push_str(&i.as_str()),
+        OperandKind::Index(identifier, ref index) => {
+            output.push_str(&format!("{}[", identifier));
+            write_expression(output, index);
+            output.push(']');
+        }
+        OperandKind::Call(name, ref arguments) => write_call(output, name, arguments),
+        OperandKind::Expr(ref expression) => {
+            if expression_precedence(expression) < min_precedence {
+                output.push('(');
+                write_expression(output, expression);
+                output.push(')');
+            } else {
+                write_expression(output, expression);
+            }
+        }
+    }
+}
+
+fn write_call(output: &mut String, name: Symbol, arguments: &[Expression]) {
+    output.push_str(&name.as_str());
+    output.push('(');
+    for (i, argument) in arguments.iter().enumerate() {
+        if i > 0 {
+            output.push_str(", ");
+        }
+        write_expression(output, argument);
+    }
+    output.push(')');
+}
+
+fn expression_precedence(expression: &Expression) -> u8 {
+    match expression.kind {
+        ExpressionKind::Binary(_, ref operator, _) => operator.precedence(),
+        ExpressionKind::Unary(..) => UNARY_PRECEDENCE,
+        ExpressionKind::Singleton(ref operand) => match operand.kind {
+            OperandKind::Expr(ref expression) => expression_precedence(expression),
+            _ => ATOM_PRECEDENCE,
+        },
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::VecDeque;
+
+use super::{format, FormatError};
+use parser::{parse, ParseErrorKind, Statement};
+use scanner::{Dialect, LexerConfig, Scanner, Token};
+
+fn format_source(source: &str) -> String {
+    format(source, LexerConfig::default()).unwrap_or_else(|e| panic!("{}", e))
+}
+
+fn parse_source(source: &str) -> Vec<Statement> {
+    let mut scanner = Scanner::new();
+    let mut tokens = VecDeque::new();
+    scanner.scan(source, &mut tokens);
+    let mut statements = Vec::new();
+    assert_eq!(parse(&mut tokens, &mut statements), vec![]);
+    statements
+}
+
+const MESSY: &str = r#"// Prints a greeting n times.
+var nTimes:int:=(0);print "How many times?" ;   read nTimes;
+
+
+/// The loop variable.
+var x : int;
+for x in 0..(nTimes-1) do print x;  // the counter
+        /* the greeting */ print " : Hello, World!\n";
+
+end for; assert ((x = nTimes)) ;
+print (1 + 2) * 3 - (4 - (5 - 6)) + 7 ; print !(a & b) | !c & (d | e) < f;
+print a /* inside */ + b;
+// The end."#;
+
+const FORMATTED: &str = r#"// Prints a greeting n times.
+var nTimes : int := 0;
+print "How many times?";
+read nTimes;
+
+/// The loop variable.
+var x : int;
+for x in 0..nTimes - 1 do
+    print x; // the counter
+    /* the greeting */
+    print " : Hello, World!\n";
+end for;
+assert (x = nTimes);
+print (1 + 2) * 3 - (4 - (5 - 6)) + 7;
+print !(a & b) | !c & (d | e) < f;
+/* inside */
+print a + b;
+// The end.
+"#;
+
+#[test]
+fn programs_are_formatted_in_the_canonical_style() {
+    assert_eq!(format_source(MESSY), FORMATTED);
+}
+
+#[test]
+fn formatting_is_idempotent_and_keeps_the_statements() {
+    let formatted = format_source(MESSY);
+    assert_eq!(format_source(&formatted), formatted);
+    assert_eq!(parse_source(&formatted), parse_source(MESSY));
+
+    let nested = "for i in 1..2 do\nfor j in i..2 do\n\n// inner\nprint i*(j+1);\nend for;\n// after\nend for;";
+    let formatted = format_source(nested);
+    assert_eq!(
+        formatted,
+        "for i in 1..2 do\n    for j in i..2 do\n        // inner\n        print i * (j + 1);\n    end for;\n    // after\nend for;\n"
+    );
+    assert_eq!(format_source(&formatted), formatted);
+    assert_eq!(parse_source(&formatted), parse_source(nested));
+}
+
+#[test]
+fn sources_with_errors_are_not_formatted() {
+    match format("print 1 +;", LexerConfig::default()) {
+        Err(FormatError::Parse(errors)) => {
+            assert_eq!(errors[0].kind, ParseErrorKind::Expected("an operand", Token::Semicolon))
+        }
+        result => panic!("expected a syntax error, found {:?}", result),
+    }
+    match format("print \"unterminated;", LexerConfig::default()) {
+        Err(FormatError::Lex(_)) => {}
+        result => panic!("expected a lexical error, found {:?}", result),
+    }
+}
+
+#[test]
+fn if_statements_are_formatted() {
+    let source = "if (x<3) then print 1;  // small\nelse\n// big\nif x=3 then print 3; end if;\nend if;\nif b then end if;";
+    let formatted = format(source, LexerConfig::new(Dialect::Extended)).unwrap();
+    assert_eq!(
+        formatted,
+        "if x < 3 then\n    print 1; // small\nelse\n    // big\n    if x = 3 then\n        print 3;\n    end if;\nend if;\nif b then\nend if;\n"
+    );
+    assert_eq!(format(&formatted, LexerConfig::new(Dialect::Extended)), Ok(formatted.clone()));
+}
+
+#[test]
+fn while_loops_are_formatted() {
+    let source = "while (i<n) do i:=i+1; // next\nwhile ! done do read done; end while;\n\n// done\nend while;";
+    let formatted = format(source, LexerConfig::new(Dialect::Extended)).unwrap();
+    assert_eq!(
+        formatted,
+        "while i < n do\n    i := i + 1; // next\n    while !done do\n        read done;\n    end while;\n\n    // done\nend while;\n"
+    );
+    assert_eq!(format(&formatted, LexerConfig::new(Dialect::Extended)), Ok(formatted.clone()));
+}
+
+#[test]
+fn procedures_are_formatted() {
+    let source = "function f( n:int,s : string ) : int do\n// body\nreturn (n+1)*g( n , (s) );\nend function;\nprocedure p() do return; end procedure;\np ( );";
+    let formatted = format(source, LexerConfig::new(Dialect::Extended)).unwrap();
+    assert_eq!(
+        formatted,
+        "function f(n : int, s : string) : int do\n    // body\n    return (n + 1) * g(n, s);\nend function;\nprocedure p() do\n    return;\nend procedure;\np();\n"
+    );
+    assert_eq!(format(&formatted, LexerConfig::new(Dialect::Extended)), Ok(formatted.clone()));
+}
+
+#[test]
+fn arrays_are_formatted() {
+    let source = "var a:array[3]of int;\na [ i+1 ]:=a[(i)]*2;";
+    let formatted = format(source, LexerConfig::new(Dialect::Extended)).unwrap();
+    assert_eq!(formatted, "var a : array [3] of int;\na[i + 1] := a[i] * 2;\n");
+    assert_eq!(format(&formatted, LexerConfig::new(Dialect::Extended)), Ok(formatted.clone()));
+}
@@ -0,0 +1,560 @@
+use num::ToPrimitive;
+use num_bigint::BigInt;
+use parser::{BinaryOperator, Expression, ExpressionKind, Operand, OperandKind, Parameter, Statement,
+             StatementKind, Type, UnaryOperator};
+use util::{Source, Span};
+use std::collections::HashMap;
+use std::io::{self, Stdout, Write};
+use std::rc::Rc;
+use symbol::Symbol;
+use self::Variable::*;
+use char_stream::CharStream;
+
+#[cfg(test)]
+mod test;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Int(BigInt),
+    String(String),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    /// The value of a variable of the type declared at ´span´ without an initial value.
+    fn default_from_type(typ: &Type, span: Span) -> Self {
+        match *typ {
+            Type::Bool => Value::Bool(false),
+            Type::Int => Value::Int(0.into()),
+            Type::Str => Value::String("".into()),
+            Type::Array(size, ref element) => {
+                let mut elements = Vec::new();
+                if elements.try_reserve_exact(size).is_err() {
+                    panic!("{}: not enough memory for an array of {} elements", span, size);
+                }
+                elements.resize(size, Value::default_from_type(element, span));
+                Value::Array(elements)
+            }
+        }
+    }
+
+    fn has_type(&self, typ: &Type) -> bool {
+        match (self, typ) {
+            (&Value::Bool(_), &Type::Bool) | (&Value::Int(_), &Type::Int) | (&Value::String(_), &Type::Str) => true,
+            (Value::Array(elements), Type::Array(size, element)) => {
+                elements.len() == *size && elements.iter().all(|e| e.has_type(element))
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether a variable holding this value can be assigned the other one.
+    fn same_type(&self, other: &Value) -> bool {
+        match (self, other) {
+            (&Value::Bool(_), &Value::Bool(_))
+            | (&Value::Int(_), &Value::Int(_))
+            | (&Value::String(_), &Value::String(_)) => true,
+            (Value::Array(a), Value::Array(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.same_type(b))
+            }
+            _ => false,
+        }
+    }
+}
+
+enum Variable {
+    Mutable(Value),
+    Immutable(Value),
+}
+
+impl Variable {
+    fn freeze(&self) -> Variable {
+        match *self {
+            Mutable(ref v) => Immutable(v.clone()),
+            Immutable(ref v) => Immutable(v.clone()),
+        }
+    }
+    fn thaw(&self) -> Variable {
+        match *self {
+            Mutable(ref v) => Mutable(v.clone()),
+            Immutable(ref v) => Mutable(v.clone()),
+        }
+    }
+}
+
+struct Procedure {
+    parameters: Vec<Parameter>,
+    /// None for procedures that aren't functions.
+    return_type: Option<Type>,
+    body: Vec<Statement>,
+}
+
+/// The activation record of a procedure call. The parameters are the first variables of the frame.
+struct Frame {
+    variables: HashMap<Symbol, Variable>,
+}
+
+/// How the execution continues after a list of statements.
+enum Flow {
+    /// All of the statements were executed.
+    Next,
+    /// A return statement ended the procedure, giving the value of a function.
+    Return(Option<Value>),
+}
+
+pub struct Interpreter<W = Stdout> {
+    /// The variables declared outside of procedures, which procedures can use too.
+    globals: HashMap<Symbol, Variable>,
+    procedures: HashMap<Symbol, Rc<Procedure>>,
+    /// The procedure calls in progress, the innermost one last.
+    frames: Vec<Frame>,
+    /// The variables in the innermost scope in the order they were declared, so that blocks can
+    /// remove the ones declared in them.
+    declared: Vec<Symbol>,
+    /// Where ´read´ takes its words from.
+    input: CharStream,
+    /// Where ´print´ writes to.
+    output: W,
+}
+
+impl Interpreter {
+    /// An interpreter that reads the standard input and prints to the standard output.
+    pub fn new() -> Self {
+        Interpreter::with_io(CharStream::from_stdin(), io::stdout())
+    }
+}
+
+impl<W: Write> Interpreter<W> {
+    pub fn with_io(input: CharStream, output: W) -> Self {
+        Interpreter {
+            globals: HashMap::new(),
+            procedures: HashMap::new(),
+            frames: Vec::new(),
+            declared: Vec::new(),
+            input,
+            output,
+        }
+    }
+
+    /// The variables declarations go to: those of the innermost call or the globals.
+    fn scope(&mut self) -> &mut HashMap<Symbol, Variable> {
+        match self.frames.last_mut() {
+            Some(frame) => &mut frame.variables,
+            None => &mut self.globals,
+        }
+    }
+
+    /// Runs the statements of an if statement or of one iteration of a loop. The variables declared
+    /// in a block are local to it, so a loop body can declare the same variable on every iteration.
+    fn execute_block(&mut self, statements: &[Statement]) -> Flow {
+        let outer = self.declared.len();
+        let flow = self.execute(statements);
+        for var in self.declared.split_off(outer) {
+            self.scope().remove(&var);
+        }
+        flow
+    }
+
+    /// Looks the variable up in the innermost call and then in the globals.
+    fn variable(&self, name: Symbol) -> Option<&Variable> {
+        self.frames
+            .last()
+            .and_then(|frame| frame.variables.get(&name))
+            .or_else(|| self.globals.get(&name))
+    }
+
+    fn variable_mut(&mut self, name: Symbol) -> Option<&mut Variable> {
+        if let Some(frame) = self.frames.last_mut() {
+            if let Some(variable) = frame.variables.get_mut(&name) {
+                return Some(variable);
+            }
+        }
+        self.globals.get_mut(&name)
+    }
+
+    /// Calls the procedure in a new frame and returns the value it returned if it is a function.
+    fn call(&mut self, name: Symbol, arguments: &[Expression], span: Span) -> Option<Value> {
+        let procedure = match self.procedures.get(&name) {
+            Some(procedure) => procedure.clone(),
+            None => panic!("{}: undefined procedure {}", span, name),
+        };
+        if arguments.len() != procedure.parameters.len() {
+            panic!(
+                "{}: {} takes {} arguments but {} were given",
+                span,
+                name,
+                procedure.parameters.len(),
+                arguments.len()
+            );
+        }
+        // The arguments are evaluated in the frame of the caller.
+        let mut frame = Frame {
+            variables: HashMap::new(),
+        };
+        for (parameter, argument) in procedure.parameters.iter().zip(arguments) {
+            let value = self.eval_expr(argument);
+            if !value.has_type(&parameter.typ) {
+                panic!("{}: argument did not evaluate to {:?}", argument.span, parameter.typ);
+            }
+            if frame.variables.insert(parameter.name, Mutable(value)).is_some() {
+                panic!("{}: {} has more than one parameter called {}", span, name, parameter.name);
+            }
+        }
+        self.frames.push(frame);
+        let outer = self.declared.len();
+        let flow = self.execute(&procedure.body);
+        self.declared.truncate(outer);
+        self.frames.pop();
+        let value = match flow {
+            Flow::Return(value) => value,
+            Flow::Next => None,
+        };
+        match (value, &procedure.return_type) {
+            (None, None) => None,
+            (Some(value), Some(typ)) => if value.has_type(typ) {
+                Some(value)
+            } else {
+                panic!("{}: {} did not return {:?}", span, name, typ)
+            },
+            (None, Some(_)) => panic!("{}: {} ended without returning a value", span, name),
+            (Some(_), None) => panic!("{}: the procedure {} returned a value", span, name),
+        }
+    }
+
+    fn eval_expr(&mut self, expr: &Expression) -> Value {
+        let span = expr.span;
+        match expr.kind {
+            ExpressionKind::Binary(ref lhs, ref op, ref rhs) => {
+                let lhs = self.eval_oprnd(lhs);
+                let rhs = self.eval_oprnd(rhs);
+                match *op {
+                    BinaryOperator::And => match (lhs, rhs) {
+                        (Value::Bool(lhs), Value::Bool(rhs)) => Value::Bool(lhs && rhs),
+                        _ => panic!("{}: non boolean operands during and.", span),
+                    },
+                    BinaryOperator::Or => match (lhs, rhs) {
+                        (Value::Bool(lhs), Value::Bool(rhs)) => Value::Bool(lhs || rhs),
+                        _ => panic!("{}: non boolean operands during or.", span),
+                    },
+                    BinaryOperator::Divide => match (lhs, rhs) {
+                        (Value::Int(lhs), Value::Int(rhs)) => Value::Int(lhs / rhs),
+                        _ => panic!("{}: non integer operands during division.", span),
+                    },
+                    BinaryOperator::Equals => match (lhs, rhs) {
+                        (Value::Bool(lhs), Value::Bool(rhs)) => Value::Bool(lhs == rhs),
+                        (Value::Int(lhs), Value::Int(rhs)) => Value::Bool(lhs == rhs),
+                        (Value::String(lhs), Value::String(rhs)) => Value::Bool(lhs == rhs),
+                        _ => panic!("{}: cannot test equality of different types", span),
+                    },
+                    BinaryOperator::NotEquals => match (lhs, rhs) {
+                        (Value::Bool(lhs), Value::Bool(rhs)) => Value::Bool(lhs != rhs),
+                        (Value::Int(lhs), Value::Int(rhs)) => Value::Bool(lhs != rhs),
+                        (Value::String(lhs), Value::String(rhs)) => Value::Bool(lhs != rhs),
+                        _ => panic!("{}: cannot test equality of different types", span),
+                    },
+                    BinaryOperator::LessThan => match (lhs, rhs) {
+                        (Value::Bool(lhs), Value::Bool(rhs)) => Value::Bool(lhs < rhs),
+                        (Value::Int(lhs), Value::Int(rhs)) => Value::Bool(lhs < rhs),
+                        (Value::String(lhs), Value::String(rhs)) => Value::Bool(lhs < rhs),
+                        _ => panic!("{}: cannot test ordering of different types", span),
+                    },
+                    BinaryOperator::GreaterThan => match (lhs, rhs) {
+                        (Value::Bool(lhs), Value::Bool(rhs)) => Value::Bool(lhs > rhs),
+                        (Value::Int(lhs), Value::Int(rhs)) => Value::Bool(lhs > rhs),
+                        (Value::String(lhs), Value::String(rhs)) => Value::Bool(lhs > rhs),
+                        _ => panic!("{}: cannot test ordering of different types", span),
+                    },
+                    BinaryOperator::LessThanOrEqual => match (lhs, rhs) {
+                        (Value::Bool(lhs), Value::Bool(rhs)) => Value::Bool(lhs <= rhs),
+                        (Value::Int(lhs), Value::Int(rhs)) => Value::Bool(lhs <= rhs),
+                        (Value::String(lhs), Value::String(rhs)) => Value::Bool(lhs <= rhs),
+                        _ => panic!("{}: cannot test ordering of different types", span),
+                    },
+                    BinaryOperator::GreaterThanOrEqual => match (lhs, rhs) {
+                        (Value::Bool(lhs), Value::Bool(rhs)) => Value::Bool(lhs >= rhs),
+                        (Value::Int(lhs), Value::Int(rhs)) => Value::Bool(lhs >= rhs),
+                        (Value::String(lhs), Value::String(rhs)) => Value::Bool(lhs >= rhs),
+                        _ => panic!("{}: cannot test ordering of different types", span),
+                    },
+                    BinaryOperator::Minus => match (lhs, rhs) {
+                        (Value::Int(lhs), Value::Int(rhs)) => Value::Int(lhs - rhs),
+                        _ => panic!("{}: non integer operands during substraction.", span),
+                    },
+                    BinaryOperator::Multiply => match (lhs, rhs) {
+                        (Value::Int(lhs), Value::Int(rhs)) => Value::Int(lhs * rhs),
+                        _ => panic!("{}: non integer operands during multiplication.", span),
+                    },
+                    BinaryOperator::Plus => match (lhs, rhs) {
+                        (Value::Int(lhs), Value::Int(rhs)) => Value::Int(lhs + rhs),
+                        (Value::String(lhs), Value::String(rhs)) => Value::String(lhs + &rhs),
+                        _ => panic!("{}: invalid operand during addition/concatenation", span),
+                    },
+                }
+            }
+            ExpressionKind::Unary(ref op, ref rhs) => match *op {
+                UnaryOperator::Not => match self.eval_oprnd(rhs) {
+                    Value::Bool(b) => Value::Bool(!b),
+                    _ => panic!("{}: Cannot apply Not to non-boolean values", span),
+                },
+            },
+            ExpressionKind::Singleton(ref oprnd) => self.eval_oprnd(oprnd),
+        }
+    }
+
+    fn eval_oprnd(&mut self, oprnd: &Operand) -> Value {
+        match oprnd.kind {
+            OperandKind::Expr(ref expr) => self.eval_expr(expr),
+            OperandKind::Identifier(iden) => match *self
+                .variable(iden)
+                .unwrap_or_else(|| panic!("{}: undefined variable {}", oprnd.span, iden))
+            {
+                Mutable(ref n) | Immutable(ref n) => n.clone(),
+            },
+            OperandKind::Index(name, ref index) => {
+                let index = self.eval_expr(index);
+                match *self
+                    .variable(name)
+                    .unwrap_or_else(|| panic!("{}: undefined variable {}", oprnd.span, name))
+                {
+                    Mutable(Value::Array(ref elements)) | Immutable(Value::Array(ref elements)) => {
+                        elements[array_index(index, elements.len(), oprnd.span)].clone()
+                    }
+                    _ => panic!("{}: {} is not an array", oprnd.span, name),
+                }
+            }
+            OperandKind::Call(name, ref arguments) => self.call(name, arguments, oprnd.span)
+                .unwrap_or_else(|| panic!("{}: the procedure {} does not return a value", oprnd.span, name)),
+            OperandKind::Int(ref n) => Value::Int(n.clone()),
+            OperandKind::StringLiteral(s) => Value::String(s.as_str().to_string()),
+            OperandKind::Bool(b) => Value::Bool(b),
+        }
+    }
+
+    pub fn interpret<S>(&mut self, statements: &mut S)
+    where
+        S: Source<Statement>,
+    {
+        while let Some(stmt) = statements.take() {
+            // Procedures are only defined at the top level, so their bodies can be moved into them
+            // instead of being copied.
+            if let StatementKind::Procedure(name, parameters, return_type, body) = stmt.kind {
+                if self.procedures.contains_key(&name) {
+                    panic!("{}: {} was already defined", stmt.span, name);
+                }
+                let procedure = Procedure {
+                    parameters,
+                    return_type,
+                    body,
+                };
+                self.procedures.insert(name, Rc::new(procedure));
+                continue;
+            }
+            // A return outside of procedures ends the program.
+            if let Flow::Return(_) = self.execute_statement(&stmt) {
+                return;
+            }
+        }
+    }
+
+    fn execute(&mut self, statements: &[Statement]) -> Flow {
+        for stmt in statements {
+            if let Flow::Return(value) = self.execute_statement(stmt) {
+                return Flow::Return(value);
+            }
+        }
+        Flow::Next
+    }
+
+    fn execute_statement(&mut self, stmt: &Statement) -> Flow {
+        let span = stmt.span;
+        match stmt.kind {
+            StatementKind::Assert(ref expr) => match self.eval_expr(expr) {
+                Value::Bool(b) => if !b {
+                    panic!("{}: assert failed!", span);
+                },
+                _ => panic!("{}: the expression did not evaluate to a boolean", span),
+            },
+            StatementKind::Assignment(var, ref expr) => {
+                let new_val = self.eval_expr(expr);
+                match self.variable_mut(var) {
+                    Some(v) => match *v {
+                        Mutable(ref mut val) => if val.same_type(&new_val) {
+                            *val = new_val;
+                        } else {
+                            panic!(
+                                "{}: expression did not evaluate to the same type as the variable",
+                                span
+                            )
+                        },
+                        Immutable(_) => panic!("{}: cannot modify {}", span, var),
+                    },
+                    None => panic!("{}: {} not initialised", span, var),
+                };
+            }
+            StatementKind::IndexedAssignment(var, ref index, ref expr) => {
+                let index = self.eval_expr(index);
+                let new_val = self.eval_expr(expr);
+                match self.variable_mut(var) {
+                    Some(&mut Mutable(Value::Array(ref mut elements))) => {
+                        let index = array_index(index, elements.len(), span);
+                        if !elements[index].same_type(&new_val) {
+                            panic!(
+                                "{}: expression did not evaluate to the type of the elements of {}",
+                                span, var
+                            );
+                        }
+                        elements[index] = new_val;
+                    }
+                    Some(&mut Mutable(_)) => panic!("{}: {} is not an array", span, var),
+                    Some(&mut Immutable(_)) => panic!("{}: cannot modify {}", span, var),
+                    None => panic!("{}: {} not initialised", span, var),
+                }
+            }
+            StatementKind::Declaration(var, ref typ, ref o_expr, _) => {
+                let value = match *o_expr {
+                    Some(ref expr) => {
+                        let val = self.eval_expr(expr);
+                        if !val.has_type(typ) {
+                            panic!("{}: expression did not evaluate to {:?}", span, typ);
+                        }
+                        val
+                    }
+                    None => Value::default_from_type(typ, span),
+                };
+                if self.scope().contains_key(&var) {
+                    panic!(
+                        "{}: Tried to initialize a variable that was already initialized",
+                        span
+                    );
+                } else {
+                    self.scope().insert(var, Mutable(value));
+                    self.declared.push(var);
+                }
+            }
+            StatementKind::For(var, ref from, ref to, ref stmts) => {
+                match self.variable_mut(var) {
+                    Some(variable) => {
+                        match *variable {
+                            Mutable(ref val) => match *val {
+                                Value::Int(_) => {}
+                                _ => panic!(
+                                    "{}: loop control variable was not an integer",
+                                    span
+                                ),
+                            },
+                            _ => panic!("{}: loop variable cannot be reused", span),
+                        }
+                        *variable = variable.freeze();
+                    }
+                    None => panic!("{}: for loop variable uninitialized", span),
+                }
+                let mut flow = Flow::Next;
+                if let Value::Int(from) = self.eval_expr(from) {
+                    if let Value::Int(to) = self.eval_expr(to) {
+                        for i in ::num::range_inclusive(from, to + &BigInt::from(1)) {
+                            if let Immutable(Value::Int(ref mut n)) =
+                                *self.variable_mut(var).unwrap()
+                            {
+                                *n = i;
+                            } else {
+                                unreachable!();
+                            }
+                            flow = self.execute_block(stmts);
+                            if let Flow::Return(_) = flow {
+                                break;
+                            }
+                        }
+                    } else {
+                        panic!("{}: range expression did not evaluate to an integer", span);
+                    }
+                } else {
+                    panic!("{}: range expression did not evaluate to an integer", span);
+                }
+                let mut control_variable = self.variable_mut(var).unwrap();
+                *control_variable = control_variable.thaw();
+                if let Flow::Return(_) = flow {
+                    return flow;
+                }
+            }
+            StatementKind::If(ref condition, ref then, ref otherwise) => {
+                let flow = match self.eval_expr(condition) {
+                    Value::Bool(true) => self.execute_block(then),
+                    Value::Bool(false) => self.execute_block(otherwise),
+                    _ => panic!("{}: the condition did not evaluate to a boolean", span),
+                };
+                if let Flow::Return(_) = flow {
+                    return flow;
+                }
+            }
+            StatementKind::While(ref condition, ref stmts) => loop {
+                match self.eval_expr(condition) {
+                    Value::Bool(true) => if let Flow::Return(value) = self.execute_block(stmts) {
+                        return Flow::Return(value);
+                    },
+                    Value::Bool(false) => break,
+                    _ => panic!("{}: the condition did not evaluate to a boolean", span),
+                }
+            },
+            StatementKind::Procedure(name, ..) => {
+                panic!("{}: {} is not defined at the top level of the program", span, name)
+            }
+            StatementKind::Call(name, ref arguments) => {
+                self.call(name, arguments, span);
+            }
+            StatementKind::Return(ref value) => {
+                return Flow::Return(value.as_ref().map(|value| self.eval_expr(value)));
+            }
+            StatementKind::Print(ref expr) => {
+                let written = match self.eval_expr(expr) {
+                    Value::Bool(b) => writeln!(self.output, "{}", b),
+                    Value::Int(i) => writeln!(self.output, "{}", i),
+                    Value::String(s) => writeln!(self.output, "{}", s),
+                    Value::Array(_) => panic!("{}: arrays cannot be printed", span),
+                };
+                if let Err(why) = written {
+                    panic!("{}: could not print: {}", span, why);
+                }
+            }
+            StatementKind::Read(var) => {
+                let input: String = self.input.by_ref().take_while(|c| !c.is_whitespace()).collect();
+                match *self
+                    .variable_mut(var)
+                    .unwrap_or_else(|| panic!("{}: variable {} was undefined", span, var))
+                {
+                    Mutable(ref mut val) => match *val {
+                        Value::Int(ref mut i) => {
+                            *i = input
+                                .parse()
+                                .unwrap_or_else(|_| panic!("{}: invalid integer input", span));
+                        }
+                        Value::String(ref mut s) => {
+                            *s = input;
+                        }
+                        Value::Bool(ref mut b) => {
+                            *b = input
+                                .parse()
+                                .unwrap_or_else(|_| panic!("{}: invalid boolean input", span));
+                        }
+                        Value::Array(_) => panic!("{}: Tried to read into an array", span),
+                    },
+                    Immutable(_) => {
+                        panic!("{}: tried to read to a loop control variable", span)
+                    }
+                }
+            }
+        }
+        Flow::Next
+    }
+}
+
+/// Checks that the index is within an array of ´len´ elements. Arrays are indexed from 0.
+fn array_index(index: Value, len: usize, span: Span) -> usize {
+    match index {
+        Value::Int(index) => match index.to_usize() {
+            Some(i) if i < len => i,
+            _ => panic!("{}: index {} is out of bounds for an array of {} elements", span, index, len),
+        },
+        _ => panic!("{}: index did not evaluate to an integer", span),
+    }
+}
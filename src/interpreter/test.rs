@@ -0,0 +1,229 @@
+use std::collections::VecDeque;
+use super::Interpreter;
+use char_stream::CharStream;
+use parser::parse;
+use scanner::{Dialect, LexerConfig, Scanner};
+
+/// Runs an extended dialect program with the given input and returns what it printed.
+fn run(source: &str, input: &str) -> String {
+    let mut scanner = Scanner::with_config(LexerConfig::new(Dialect::Extended));
+    let mut tokens = VecDeque::new();
+    scanner.scan(source, &mut tokens);
+    scanner.finish(&mut tokens);
+    assert_eq!(scanner.errors(), &[][..], "{}", source);
+    let mut statements = VecDeque::new();
+    let errors = parse(&mut tokens, &mut statements);
+    assert_eq!(errors, vec![], "{}", source);
+    let mut output = Vec::new();
+    Interpreter::with_io(CharStream::from(input), &mut output).interpret(&mut statements);
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn if_statements_run_the_branch_of_the_condition() {
+    let source = "var x : int := 3;
+if x < 5 then
+  print \"small\";
+else
+  print \"big\";
+end if;
+if x = 5 then
+  print \"five\";
+else
+  print \"not five\";
+end if;
+if x = 3 then
+  print \"three\";
+end if;
+if x = 4 then
+  print \"four\";
+end if;";
+    assert_eq!(run(source, ""), "small\nnot five\nthree\n");
+}
+
+#[test]
+#[should_panic(expected = "1:1: the condition did not evaluate to a boolean")]
+fn if_conditions_have_to_be_booleans() {
+    run("if 1 then\n  print 1;\nend if;", "");
+}
+
+#[test]
+fn while_loops_check_the_condition_before_every_iteration() {
+    let source = "var i : int := 0;
+while i < 3 do
+  print i;
+  i := i + 1;
+end while;
+while i < 3 do
+  print \"never\";
+end while;
+print i;";
+    assert_eq!(run(source, ""), "0\n1\n2\n3\n");
+}
+
+#[test]
+fn declarations_in_blocks_are_local_to_them() {
+    let source = "var i : int := 0;
+while i < 2 do
+  var square : int := i * i;
+  if i = 1 then
+    var word : string := \"odd\";
+    print word;
+  end if;
+  print square;
+  i := i + 1;
+end while;
+var square : string := \"after\";
+print square;
+if i = 2 then
+  var word : int := 2;
+else
+  var word : int := 3;
+end if;
+var j : int;
+for j in 1..2 do
+  var word : int := 2 * j;
+end for;
+var word : string := \"done\";
+print word;";
+    assert_eq!(run(source, ""), "0\nodd\n1\nafter\ndone\n");
+}
+
+#[test]
+#[should_panic(expected = "6:7: undefined variable square")]
+fn declarations_in_loops_are_gone_after_the_loop() {
+    run("var i : int := 0;\nwhile i < 2 do\n  var square : int := i * i;\n  i := i + 1;\nend while;\nprint square;", "");
+}
+
+#[test]
+fn functions_can_call_themselves() {
+    let source = "function factorial(n : int) : int do
+  if n < 2 then
+    return 1;
+  end if;
+  return n * factorial(n - 1);
+end function;
+print factorial(5);
+print factorial(25);";
+    assert_eq!(run(source, ""), "120\n15511210043330985984000000\n");
+}
+
+#[test]
+fn returns_leave_the_procedure_from_inside_of_blocks() {
+    let source = "function find(target : int) : string do
+  var i : int := 0;
+  while 0 = 0 do
+    if i = target then
+      var j : int;
+      for j in 0..i do
+        if j = i then
+          return \"found\";
+        end if;
+      end for;
+    end if;
+    i := i + 1;
+  end while;
+end function;
+procedure greet(name : string) do
+  while 0 = 0 do
+    print \"hello \" + name;
+    return;
+  end while;
+  print \"unreachable\";
+end procedure;
+print find(3);
+greet(\"world\");
+print find(1);";
+    assert_eq!(run(source, ""), "found\nhello world\nfound\n");
+}
+
+#[test]
+fn declarations_in_procedures_stay_in_their_call() {
+    let source = "procedure count(n : int) do
+  var i : int := n;
+  print i;
+end procedure;
+var i : int := 0;
+while i < 2 do
+  count(i + 10);
+  i := i + 1;
+end while;
+print i;";
+    assert_eq!(run(source, ""), "10\n11\n2\n");
+}
+
+#[test]
+#[should_panic(expected = "4:7: twice takes 1 arguments but 2 were given")]
+fn calls_need_an_argument_for_every_parameter() {
+    run("function twice(n : int) : int do\n  return 2 * n;\nend function;\nprint twice(1, 2);", "");
+}
+
+#[test]
+#[should_panic(expected = "4:13: argument did not evaluate to Int")]
+fn arguments_need_the_types_of_the_parameters() {
+    run("function twice(n : int) : int do\n  return 2 * n;\nend function;\nprint twice(\"1\");", "");
+}
+
+#[test]
+#[should_panic(expected = "7:7: sign ended without returning a value")]
+fn functions_have_to_return_a_value() {
+    let source = "function sign(n : int) : int do\n  if n < 0 then\n    return 0 - 1;\n  end if;\nend function;\nprint sign(0 - 5);\nprint sign(5);";
+    run(source, "");
+}
+
+#[test]
+fn arrays_are_indexed_from_zero() {
+    let source = "var a : array [3] of int;
+var i : int := 0;
+while i < 3 do
+  a[i] := i * i;
+  i := i + 1;
+end while;
+print a[0] + a[1] + a[2];
+var b : array [3] of int := a;
+b[2] := 0;
+print a[2];
+print b[2];";
+    assert_eq!(run(source, ""), "5\n4\n0\n");
+}
+
+#[test]
+#[should_panic(expected = "2:7: index 3 is out of bounds for an array of 3 elements")]
+fn array_indices_have_to_be_below_the_size() {
+    run("var a : array [3] of int;\nprint a[3];", "");
+}
+
+#[test]
+#[should_panic(expected = "2:1: index -1 is out of bounds for an array of 3 elements")]
+fn array_indices_cannot_be_negative() {
+    run("var a : array [3] of string;\na[0 - 1] := \"x\";", "");
+}
+
+#[test]
+#[should_panic(expected = "2:7: index did not evaluate to an integer")]
+fn array_indices_have_to_be_integers() {
+    run("var a : array [3] of bool;\nprint a[\"0\"];", "");
+}
+
+#[test]
+fn booleans_are_printed_and_read_as_words() {
+    let source = "var b : bool;
+var n : int;
+var s : string;
+print b;
+read b;
+print !b;
+read n;
+read s;
+read b;
+print b = (n = 12);
+print s;";
+    assert_eq!(run(source, "true 12 word false"), "false\nfalse\nfalse\nword\n");
+    assert_eq!(run("var b : bool;\nread b;\nprint b | false;", "true\n"), "true\n");
+}
+
+#[test]
+#[should_panic(expected = "2:1: invalid boolean input")]
+fn only_true_and_false_are_read_as_booleans() {
+    run("var b : bool;\nread b;", "1");
+}
@@ -2,43 +2,185 @@ extern crate char_stream;
 extern crate num;
 extern crate num_bigint;
 extern crate rayon;
+#[macro_use]
+extern crate serde_json;
+extern crate unicode_normalization;
+extern crate unicode_xid;
 
-use std::sync::mpsc::channel;
-use std::sync::mpsc::Sender;
-use std::sync::mpsc::Receiver;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
 
-use std::process::abort;
+use std::process;
 
 use rayon::ThreadPoolBuilder;
 
 pub mod file_handling;
+pub mod formatter;
 pub mod scanner;
 pub mod parser;
 pub mod interpreter;
+pub mod symbol;
 pub mod util;
 
-use scanner::Scanner;
+use scanner::{LexerConfig, Scanner, SpannedToken};
 use interpreter::Interpreter;
+use parser::{ParseError, Statement};
+use util::{Sink, Source};
 
 //use scanner::Scanner;
 
 fn main() {
-    let pool = ThreadPoolBuilder::new().num_threads(3).build().unwrap();
-    let (mut token_sink, mut token_source) = channel();
-    let (mut statement_sink, mut statement_source) = channel();
-    let (mut kill_switch, mut kill_signal) = channel();
+    if file_handling::is_format_command() {
+        let config = LexerConfig::new(file_handling::get_dialect());
+        match formatter::format(&file_handling::get_source_text(), config) {
+            Ok(formatted) => print!("{}", formatted),
+            Err(errors) => {
+                eprintln!("{}", errors);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(format) = file_handling::get_ast_format("emit") {
+        // Prints the syntax tree of the program instead of running it.
+        let config = LexerConfig::new(file_handling::get_dialect());
+        let mut scanner = Scanner::with_config(config);
+        let mut tokens = VecDeque::new();
+        scanner.scan(&file_handling::get_source_text(), &mut tokens);
+        scanner.finish(&mut tokens);
+        let mut statements = Vec::new();
+        let errors = parser::parse(&mut tokens, &mut statements);
+        for error in scanner.errors() {
+            eprintln!("{}", error);
+        }
+        for error in &errors {
+            eprintln!("{}", error);
+        }
+        if !scanner.errors().is_empty() || !errors.is_empty() {
+            process::exit(1);
+        }
+        println!("{}", parser::serialize(&statements, format));
+        return;
+    }
+    if let Some(format) = file_handling::get_ast_format("load") {
+        // Runs a syntax tree made by another tool.
+        let statements = parser::deserialize(&file_handling::get_source_text(), format)
+            .unwrap_or_else(|why| {
+                eprintln!("{}", why);
+                process::exit(1)
+            });
+        Interpreter::new().interpret(&mut VecDeque::from(statements));
+        return;
+    }
+    // The scanner, the parser and the interpreter run side by side, so the program starts running
+    // before all of it has been read. Everything stops at the first scan or parse error: no
+    // statement after it is run and the process exits with an error.
+    let pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    let (token_sink, token_source) = channel();
+    let (statement_sink, mut statement_source) = channel();
+    let failed = Arc::new(AtomicBool::new(false));
+    let parse_failed = failed.clone();
 
     pool.spawn(move || {
-        let mut scanner = Scanner::new();
-        scanner.scan(&file_handling::get_source_text(), &mut token_sink);
+        let config = LexerConfig::new(file_handling::get_dialect());
+        let mut scanner = Scanner::with_config(config);
+        let mut source = BufReader::new(file_handling::open_source());
+        let mut line = Vec::new();
+        let mut tokens = Vec::new();
+        let mut reported = 0;
+        // The source is scanned a line at a time so that the tokens of a line can be passed on
+        // after the mark of the errors found in it.
+        let mut done = false;
+        while !done {
+            line.clear();
+            match source.read_until(b'\n', &mut line) {
+                Ok(0) => done = true,
+                Ok(_) => scanner
+                    .scan_reader(&line[..], &mut tokens)
+                    .expect("reading from memory failed"),
+                Err(why) => {
+                    eprintln!("couldn't read the source: {}", why);
+                    token_sink.send(None).unwrap();
+                    done = true;
+                }
+            }
+            if done {
+                scanner.finish(&mut tokens);
+            }
+            if scanner.errors().len() > reported {
+                for error in &scanner.errors()[reported..] {
+                    eprintln!("{}", error);
+                }
+                reported = scanner.errors().len();
+                token_sink.send(None).unwrap();
+            }
+            for token in tokens.drain(..) {
+                token_sink.send(Some(token)).unwrap();
+            }
+        }
     });
     pool.spawn(move || {
-        parser::parse(&mut token_source, &mut statement_sink);
+        let mut tokens = ScannedTokens {
+            tokens: token_source,
+            failed: parse_failed.clone(),
+        };
+        let mut statements = UntilFailed {
+            statements: statement_sink,
+            failed: parse_failed.clone(),
+        };
+        parser::parse_reporting(&mut tokens, &mut statements, &mut ErrorReporter(parse_failed));
     });
-    pool.spawn(move || {
-        let mut interpreter = Interpreter::new();
-        interpreter.interpret(&mut statement_source);
-        kill_switch.send(true);
-    });
-    kill_signal.recv();
+    Interpreter::new().interpret(&mut statement_source);
+    // The parser lets go of its end of the channel last, so once it's closed every error has been
+    // printed.
+    while statement_source.recv().is_ok() {}
+    if failed.load(Ordering::SeqCst) {
+        process::exit(1);
+    }
+}
+
+/// Tokens coming from the scanner thread. A ´None´ in between them marks a scan error and the
+/// program as failed.
+struct ScannedTokens {
+    tokens: Receiver<Option<SpannedToken>>,
+    failed: Arc<AtomicBool>,
+}
+
+impl Source<SpannedToken> for ScannedTokens {
+    fn take(&mut self) -> Option<SpannedToken> {
+        loop {
+            match self.tokens.recv().ok()? {
+                Some(token) => return Some(token),
+                None => self.failed.store(true, Ordering::SeqCst),
+            }
+        }
+    }
+}
+
+/// Passes statements on to the interpreter until the program is known to have errors.
+struct UntilFailed {
+    statements: Sender<Statement>,
+    failed: Arc<AtomicBool>,
+}
+
+impl Sink<Statement> for UntilFailed {
+    fn put(&mut self, statement: Statement) {
+        if !self.failed.load(Ordering::SeqCst) {
+            // The main thread is only gone if the program crashed.
+            let _ = self.statements.send(statement);
+        }
+    }
+}
+
+/// Prints syntax errors as soon as they are found and marks the program as failed.
+struct ErrorReporter(Arc<AtomicBool>);
+
+impl Sink<ParseError> for ErrorReporter {
+    fn put(&mut self, error: ParseError) {
+        eprintln!("{}", error);
+        self.0.store(true, Ordering::SeqCst);
+    }
 }
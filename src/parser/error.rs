@@ -0,0 +1,91 @@
+use std::fmt;
+
+use scanner::Token;
+use super::{MAX_ARRAY_SIZE, MAX_NESTING};
+use util::Span;
+
+/// A syntax error found during parsing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Span,
+}
+
+/// All the different kinds of syntax errors the parser can report.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseErrorKind {
+    /// Something else was expected than the token that was found.
+    /// The description of what was expected is written to be read after "expected".
+    Expected(&'static str, Token),
+    /// The input ended in the middle of a statement.
+    UnexpectedEnd(&'static str),
+    /// An ´end´ that doesn't end any block.
+    UnmatchedEnd,
+    /// An ´else´ that isn't in the then branch of an if statement.
+    UnmatchedElse,
+    /// The input ended before the ´end for´ of a for loop.
+    UnclosedFor,
+    /// The input ended before the ´end if´ of an if statement.
+    UnclosedIf,
+    /// The input ended before the ´end while´ of a while loop.
+    UnclosedWhile,
+    /// The input ended before the ´end procedure´ or ´end function´ of a procedure.
+    UnclosedProcedure,
+    /// A procedure defined inside a block.
+    NestedProcedure,
+    /// A ´return´ that isn't in a procedure.
+    ReturnOutsideProcedure,
+    /// An array type with more than ´MAX_ARRAY_SIZE´ elements.
+    ArrayTooLarge,
+    /// An expression nested deeper than ´MAX_NESTING´ levels.
+    NestedTooDeeply,
+    /// A doc comment inside of a statement instead of in front of one.
+    MisplacedDocComment,
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::ParseErrorKind::*;
+        match *self {
+            Expected(expected, ref found) => write!(f, "expected {} but found {}", expected, found),
+            UnexpectedEnd(expected) => write!(f, "expected {} but the input ended", expected),
+            UnmatchedEnd => write!(f, "found an end without a block to end"),
+            UnmatchedElse => write!(f, "found an else without an if statement"),
+            UnclosedFor => write!(f, "the for loop is missing its end for"),
+            UnclosedIf => write!(f, "the if statement is missing its end if"),
+            UnclosedWhile => write!(f, "the while loop is missing its end while"),
+            UnclosedProcedure => write!(f, "the procedure is missing its end"),
+            NestedProcedure => write!(f, "procedures can only be defined at the top level of the program"),
+            ReturnOutsideProcedure => write!(f, "found a return outside of a procedure"),
+            ArrayTooLarge => write!(f, "arrays can have at most {} elements", MAX_ARRAY_SIZE),
+            NestedTooDeeply => write!(f, "expressions can be nested at most {} levels deep", MAX_NESTING),
+            MisplacedDocComment => write!(f, "doc comments can only be in front of statements"),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.kind)
+    }
+}
+
+/// A syntax tree read from JSON or S-expressions that doesn't describe a valid program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoadError {
+    pub message: String,
+}
+
+impl LoadError {
+    pub fn new<S: Into<String>>(message: S) -> Self {
+        LoadError {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid syntax tree: {}", self.message)
+    }
+}
@@ -0,0 +1,434 @@
+// Syntax trees as JSON. A program is an array of statements and every node is an object with a
+// "type" member telling what the node is and an optional "span" member telling where in the
+// source the node was parsed from:
+//
+//   "span": {"start": <byte offset>, "end": <byte offset>, "line": <number>, "column": <number>}
+//
+// Statements:
+//   {"type": "declaration", "name": <string>, "var_type": <type>,
+//    "value": <expression> | null, "docs": [<string>, ...]}
+//   {"type": "assignment", "name": <string>, "value": <expression>}
+//   {"type": "indexed_assignment", "name": <string>, "index": <expression>, "value": <expression>}
+//   {"type": "for", "variable": <string>, "from": <expression>, "to": <expression>,
+//    "body": [<statement>, ...]}
+//   {"type": "if", "condition": <expression>, "then": [<statement>, ...],
+//    "else": [<statement>, ...]}
+//   {"type": "while", "condition": <expression>, "body": [<statement>, ...]}
+//   {"type": "procedure", "name": <string>, "parameters": [{"name": <string>, "var_type": <type>},
+//    ...], "return_type": <type> | null, "body": [<statement>, ...]}
+//   {"type": "call", "name": <string>, "arguments": [<expression>, ...]}
+//   {"type": "return", "value": <expression> | null}
+//   {"type": "read", "name": <string>}
+//   {"type": "print", "value": <expression>}
+//   {"type": "assert", "value": <expression>}
+//
+// Expressions:
+//   {"type": "binary", "operator": "+" | "-" | "*" | "/" | "<" | ">" | "<=" | ">=" | "=" | "!="
+//    | "&" | "|", "left": <expression>, "right": <expression>}
+//   {"type": "unary", "operator": "!", "operand": <expression>}
+//   {"type": "int", "value": <number> | <string of decimal digits>}
+//   {"type": "string", "value": <string>}
+//   {"type": "bool", "value": <boolean>}
+//   {"type": "identifier", "name": <string>}
+//   {"type": "index", "name": <string>, "index": <expression>}
+//   {"type": "call", "name": <string>, "arguments": [<expression>, ...]}
+//
+// Types are written as "int", "string" or "bool" and array types as
+// {"size": <number>, "element": <type>}. Integers that don't fit into 64 bits are
+// written as strings. The "value" and "docs" members of declarations, the "else" member of if
+// statements, the "return_type" member of procedures and the "value" member of return statements
+// can be left out.
+
+use std::str::FromStr;
+
+use num::ToPrimitive;
+use num_bigint::BigInt;
+use serde_json::Value;
+
+use symbol::Symbol;
+use util::Span;
+
+use super::{check_structure, BinaryOperator, Expression, ExpressionKind, LoadError, NodeId, Operand, OperandKind,
+            Parameter, ParseErrorKind, Statement, StatementKind, Type, UnaryOperator, MAX_ARRAY_SIZE};
+
+pub fn to_json(statements: &[Statement]) -> Value {
+    Value::Array(statements.iter().map(statement_to_json).collect())
+}
+
+fn statement_to_json(statement: &Statement) -> Value {
+    let mut node = match statement.kind {
+        StatementKind::Declaration(name, ref typ, ref value, ref docs) => json!({
+            "type": "declaration",
+            "name": name.to_string(),
+            "var_type": type_to_json(typ),
+            "value": value.as_ref().map(expression_to_json),
+            "docs": docs.iter().map(|doc| doc.to_string()).collect::<Vec<_>>(),
+        }),
+        StatementKind::Assignment(name, ref value) => json!({
+            "type": "assignment",
+            "name": name.to_string(),
+            "value": expression_to_json(value),
+        }),
+        StatementKind::IndexedAssignment(name, ref index, ref value) => json!({
+            "type": "indexed_assignment",
+            "name": name.to_string(),
+            "index": expression_to_json(index),
+            "value": expression_to_json(value),
+        }),
+        StatementKind::For(variable, ref from, ref to, ref body) => json!({
+            "type": "for",
+            "variable": variable.to_string(),
+            "from": expression_to_json(from),
+            "to": expression_to_json(to),
+            "body": to_json(body),
+        }),
+        StatementKind::If(ref condition, ref then, ref otherwise) => json!({
+            "type": "if",
+            "condition": expression_to_json(condition),
+            "then": to_json(then),
+            "else": to_json(otherwise),
+        }),
+        StatementKind::While(ref condition, ref body) => json!({
+            "type": "while",
+            "condition": expression_to_json(condition),
+            "body": to_json(body),
+        }),
+        StatementKind::Procedure(name, ref parameters, ref return_type, ref body) => json!({
+            "type": "procedure",
+            "name": name.to_string(),
+            "parameters": parameters
+                .iter()
+                .map(|p| json!({"name": p.name.to_string(), "var_type": type_to_json(&p.typ)}))
+                .collect::<Vec<_>>(),
+            "return_type": return_type.as_ref().map(type_to_json),
+            "body": to_json(body),
+        }),
+        StatementKind::Call(name, ref arguments) => call_to_json(name, arguments),
+        StatementKind::Return(ref value) => json!({
+            "type": "return",
+            "value": value.as_ref().map(expression_to_json),
+        }),
+        StatementKind::Read(name) => json!({"type": "read", "name": name.to_string()}),
+        StatementKind::Print(ref value) => json!({"type": "print", "value": expression_to_json(value)}),
+        StatementKind::Assert(ref value) => json!({"type": "assert", "value": expression_to_json(value)}),
+    };
+    node["span"] = span_to_json(statement.span);
+    node
+}
+
+fn expression_to_json(expression: &Expression) -> Value {
+    let mut node = match expression.kind {
+        ExpressionKind::Binary(ref left, ref operator, ref right) => json!({
+            "type": "binary",
+            "operator": operator.to_string(),
+            "left": operand_to_json(left),
+            "right": operand_to_json(right),
+        }),
+        ExpressionKind::Unary(ref operator, ref operand) => json!({
+            "type": "unary",
+            "operator": operator.to_string(),
+            "operand": operand_to_json(operand),
+        }),
+        // Single operands are written as the operand.
+        ExpressionKind::Singleton(ref operand) => return operand_to_json(operand),
+    };
+    node["span"] = span_to_json(expression.span);
+    node
+}
+
+fn operand_to_json(operand: &Operand) -> Value {
+    let mut node = match operand.kind {
+        OperandKind::Int(ref n) => match n.to_i64() {
+            Some(n) => json!({"type": "int", "value": n}),
+            None => json!({"type": "int", "value": n.to_string()}),
+        },
+        OperandKind::StringLiteral(s) => json!({"type": "string", "value": s.to_string()}),
+        OperandKind::Bool(b) => json!({"type": "bool", "value": b}),
+        OperandKind::Identifier(name) => json!({"type": "identifier", "name": name.to_string()}),
+        OperandKind::Index(name, ref index) => json!({
+            "type": "index",
+            "name": name.to_string(),
+            "index": expression_to_json(index),
+        }),
+        OperandKind::Call(name, ref arguments) => call_to_json(name, arguments),
+        // Brackets are not needed because the tree already groups the operands.
+        OperandKind::Expr(ref expression) => return expression_to_json(expression),
+    };
+    node["span"] = span_to_json(operand.span);
+    node
+}
+
+fn type_to_json(typ: &Type) -> Value {
+    match *typ {
+        Type::Array(size, ref element) => json!({"size": size, "element": type_to_json(element)}),
+        ref typ => Value::String(typ.to_string()),
+    }
+}
+
+fn call_to_json(name: Symbol, arguments: &[Expression]) -> Value {
+    json!({
+        "type": "call",
+        "name": name.to_string(),
+        "arguments": arguments.iter().map(expression_to_json).collect::<Vec<_>>(),
+    })
+}
+
+fn span_to_json(span: Span) -> Value {
+    json!({
+        "start": span.start,
+        "end": span.end,
+        "line": span.line,
+        "column": span.column,
+    })
+}
+
+pub fn from_json(value: &Value) -> Result<Vec<Statement>, LoadError> {
+    let statements = statements_from_json(value)?;
+    check_structure(&statements, true, false)?;
+    Ok(statements)
+}
+
+fn statements_from_json(value: &Value) -> Result<Vec<Statement>, LoadError> {
+    match *value {
+        Value::Array(ref statements) => statements.iter().map(statement_from_json).collect(),
+        _ => Err(LoadError::new("expected an array of statements")),
+    }
+}
+
+fn statement_from_json(node: &Value) -> Result<Statement, LoadError> {
+    let kind = match node_type(node)? {
+        "declaration" => {
+            let typ = typ(node, "var_type")?;
+            let value = match node.get("value") {
+                None | Some(&Value::Null) => None,
+                Some(value) => Some(expression_from_json(value)?),
+            };
+            let docs = match node.get("docs") {
+                None => Vec::new(),
+                Some(Value::Array(docs)) => docs.iter()
+                    .map(|doc| match doc.as_str() {
+                        Some(doc) => Ok(Symbol::intern(doc)),
+                        None => Err(LoadError::new("expected doc comments to be strings")),
+                    })
+                    .collect::<Result<_, _>>()?,
+                Some(_) => return Err(LoadError::new("expected \"docs\" to be an array")),
+            };
+            StatementKind::Declaration(symbol(node, "name")?, typ, value, docs)
+        }
+        "assignment" => StatementKind::Assignment(symbol(node, "name")?, expression_from_json(field(node, "value")?)?),
+        "for" => StatementKind::For(
+            symbol(node, "variable")?,
+            expression_from_json(field(node, "from")?)?,
+            expression_from_json(field(node, "to")?)?,
+            statements_from_json(field(node, "body")?)?,
+        ),
+        "if" => StatementKind::If(
+            expression_from_json(field(node, "condition")?)?,
+            statements_from_json(field(node, "then")?)?,
+            match node.get("else") {
+                Some(otherwise) => statements_from_json(otherwise)?,
+                None => Vec::new(),
+            },
+        ),
+        "while" => StatementKind::While(
+            expression_from_json(field(node, "condition")?)?,
+            statements_from_json(field(node, "body")?)?,
+        ),
+        "procedure" => {
+            let parameters = match *field(node, "parameters")? {
+                Value::Array(ref parameters) => parameters
+                    .iter()
+                    .map(|parameter| {
+                        Ok(Parameter {
+                            name: symbol(parameter, "name")?,
+                            typ: typ(parameter, "var_type")?,
+                        })
+                    })
+                    .collect::<Result<_, _>>()?,
+                _ => return Err(LoadError::new("expected \"parameters\" to be an array")),
+            };
+            let return_type = match node.get("return_type") {
+                None | Some(&Value::Null) => None,
+                Some(_) => Some(typ(node, "return_type")?),
+            };
+            let body = statements_from_json(field(node, "body")?)?;
+            StatementKind::Procedure(symbol(node, "name")?, parameters, return_type, body)
+        }
+        "indexed_assignment" => StatementKind::IndexedAssignment(
+            symbol(node, "name")?,
+            expression_from_json(field(node, "index")?)?,
+            expression_from_json(field(node, "value")?)?,
+        ),
+        "call" => StatementKind::Call(symbol(node, "name")?, arguments(node)?),
+        "return" => StatementKind::Return(match node.get("value") {
+            None | Some(&Value::Null) => None,
+            Some(value) => Some(expression_from_json(value)?),
+        }),
+        "read" => StatementKind::Read(symbol(node, "name")?),
+        "print" => StatementKind::Print(expression_from_json(field(node, "value")?)?),
+        "assert" => StatementKind::Assert(expression_from_json(field(node, "value")?)?),
+        other => return Err(LoadError::new(format!("unknown statement type {:?}", other))),
+    };
+    Ok(Statement {
+        id: NodeId::DUMMY,
+        span: span_from_json(node)?,
+        kind,
+    })
+}
+
+fn expression_from_json(node: &Value) -> Result<Expression, LoadError> {
+    let kind = match node_type(node)? {
+        "binary" => {
+            let operator = match string(node, "operator")? {
+                "+" => BinaryOperator::Plus,
+                "-" => BinaryOperator::Minus,
+                "*" => BinaryOperator::Multiply,
+                "/" => BinaryOperator::Divide,
+                "<" => BinaryOperator::LessThan,
+                ">" => BinaryOperator::GreaterThan,
+                "<=" => BinaryOperator::LessThanOrEqual,
+                ">=" => BinaryOperator::GreaterThanOrEqual,
+                "=" => BinaryOperator::Equals,
+                "!=" => BinaryOperator::NotEquals,
+                "&" => BinaryOperator::And,
+                "|" => BinaryOperator::Or,
+                operator => return Err(LoadError::new(format!("unknown binary operator {:?}", operator))),
+            };
+            let left = operand_from_json(field(node, "left")?)?;
+            let right = operand_from_json(field(node, "right")?)?;
+            ExpressionKind::Binary(left, operator, right)
+        }
+        "unary" => {
+            let operator = match string(node, "operator")? {
+                "!" => UnaryOperator::Not,
+                operator => return Err(LoadError::new(format!("unknown unary operator {:?}", operator))),
+            };
+            ExpressionKind::Unary(operator, operand_from_json(field(node, "operand")?)?)
+        }
+        _ => {
+            let operand = operand_from_json(node)?;
+            return Ok(Expression {
+                id: NodeId::DUMMY,
+                span: operand.span,
+                kind: ExpressionKind::Singleton(operand),
+            });
+        }
+    };
+    Ok(Expression {
+        id: NodeId::DUMMY,
+        span: span_from_json(node)?,
+        kind,
+    })
+}
+
+fn operand_from_json(node: &Value) -> Result<Operand, LoadError> {
+    let kind = match node_type(node)? {
+        "int" => {
+            let value = field(node, "value")?;
+            let n = match *value {
+                Value::Number(ref n) => BigInt::from_str(&n.to_string()).ok(),
+                Value::String(ref n) => BigInt::from_str(n).ok(),
+                _ => None,
+            };
+            match n {
+                Some(n) => OperandKind::Int(n),
+                None => return Err(LoadError::new(format!("{} is not an integer", value))),
+            }
+        }
+        "string" => OperandKind::StringLiteral(symbol(node, "value")?),
+        "bool" => {
+            let value = field(node, "value")?;
+            match value.as_bool() {
+                Some(b) => OperandKind::Bool(b),
+                None => return Err(LoadError::new(format!("{} is not a boolean", value))),
+            }
+        }
+        "identifier" => OperandKind::Identifier(symbol(node, "name")?),
+        "index" => OperandKind::Index(
+            symbol(node, "name")?,
+            Box::new(expression_from_json(field(node, "index")?)?),
+        ),
+        "call" => OperandKind::Call(symbol(node, "name")?, arguments(node)?),
+        "binary" | "unary" => OperandKind::Expr(Box::new(expression_from_json(node)?)),
+        other => return Err(LoadError::new(format!("unknown expression type {:?}", other))),
+    };
+    Ok(Operand {
+        id: NodeId::DUMMY,
+        span: span_from_json(node)?,
+        kind,
+    })
+}
+
+fn arguments(node: &Value) -> Result<Vec<Expression>, LoadError> {
+    match *field(node, "arguments")? {
+        Value::Array(ref arguments) => arguments.iter().map(expression_from_json).collect(),
+        _ => Err(LoadError::new("expected \"arguments\" to be an array")),
+    }
+}
+
+/// Nodes without a span get an empty one.
+fn span_from_json(node: &Value) -> Result<Span, LoadError> {
+    let span = match node.get("span") {
+        None => return Ok(Span::default()),
+        Some(span) => span,
+    };
+    let number = |name| match span.get(name).and_then(Value::as_u64) {
+        Some(n) => Ok(n as usize),
+        None => Err(LoadError::new(format!("expected the span to have a number {:?}", name))),
+    };
+    Ok(Span {
+        start: number("start")?,
+        end: number("end")?,
+        line: number("line")?,
+        column: number("column")?,
+    })
+}
+
+fn node_type(node: &Value) -> Result<&str, LoadError> {
+    string(node, "type")
+}
+
+fn field<'v>(node: &'v Value, name: &str) -> Result<&'v Value, LoadError> {
+    node.get(name)
+        .ok_or_else(|| LoadError::new(format!("expected {} to have a member {:?}", node, name)))
+}
+
+fn string<'v>(node: &'v Value, name: &str) -> Result<&'v str, LoadError> {
+    field(node, name)?
+        .as_str()
+        .ok_or_else(|| LoadError::new(format!("expected {:?} of {} to be a string", name, node)))
+}
+
+fn typ(node: &Value, name: &str) -> Result<Type, LoadError> {
+    type_from_json(field(node, name)?)
+}
+
+/// Array types can only have elements of the other types.
+fn type_from_json(typ: &Value) -> Result<Type, LoadError> {
+    match *typ {
+        Value::String(ref typ) => match typ.as_str() {
+            "int" => Ok(Type::Int),
+            "string" => Ok(Type::Str),
+            "bool" => Ok(Type::Bool),
+            typ => Err(LoadError::new(format!("unknown type {:?}", typ))),
+        },
+        Value::Object(_) => {
+            let size = match field(typ, "size")?.as_u64() {
+                Some(size) => size as usize,
+                None => return Err(LoadError::new(format!("expected the size of {} to be a number", typ))),
+            };
+            if size > MAX_ARRAY_SIZE {
+                return Err(LoadError::new(ParseErrorKind::ArrayTooLarge.to_string()));
+            }
+            match type_from_json(field(typ, "element")?)? {
+                Type::Array(..) => Err(LoadError::new("expected the elements of an array not to be arrays")),
+                element => Ok(Type::Array(size, Box::new(element))),
+            }
+        }
+        _ => Err(LoadError::new(format!("expected {} to be a type", typ))),
+    }
+}
+
+fn symbol(node: &Value, name: &str) -> Result<Symbol, LoadError> {
+    string(node, name).map(Symbol::intern)
+}
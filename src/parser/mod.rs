@@ -14,32 +14,90 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::fmt;
+use std::mem;
 use std::ops::Deref;
+use std::slice;
+use std::str::FromStr;
 
+use num::ToPrimitive;
 use num_bigint::BigInt;
 
-use util::{Direction, Sink, Source};
+use symbol::Symbol;
+use util::{Direction, Sink, Source, Span};
 
-use scanner::{KeyWord, Operator, Token};
+use scanner::{KeyWord, Operator, SpannedToken, Token};
 
+pub use self::error::{LoadError, ParseError, ParseErrorKind};
+pub use self::json::{from_json, to_json};
+pub use self::sexp::{from_sexp, to_sexp};
+pub use self::visit::{walk_expression, walk_fold_expression, walk_fold_operand, walk_fold_statement,
+                      walk_operand, walk_statement, Folder, Visitor};
+
+mod error;
+mod json;
+mod sexp;
 #[cfg(test)]
 mod test;
+mod visit;
+
+//All of these enums make up our AST. Every node of the tree is a struct that has the node's
+// ID and location in the source and a ´kind´ enum that tells what the node is.
+
+/// Identifies a node of the syntax tree. IDs are handed out in the order the nodes are parsed so
+/// parsing the same source always gives the same IDs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(pub u32);
 
-//All of these enums make up our AST.
+impl NodeId {
+    /// The ID of nodes that weren't parsed from a source, e.g. nodes built by hand.
+    pub const DUMMY: NodeId = NodeId(u32::MAX);
+}
+
+/// Nodes are compared by their contents. IDs and spans are ignored so that the same program
+/// parsed from differently formatted sources gives equal trees.
+#[derive(Clone, Debug)]
+pub struct Statement {
+    pub id: NodeId,
+    pub span: Span,
+    pub kind: StatementKind,
+}
 
 //  <stmt> ::=
 //    "var" <var_ident> ":" <type> [ ":=" <expr> ]
 //  | <var_ident> ":=" <expr>
+//  | <var_ident> "[" <expr> "]" ":=" <expr>
 //  | "for" <var_ident> "in" <expr> ".." <expr> "do" <stmts> "end" "for"
+//  | "if" <expr> "then" <stmts> [ "else" <stmts> ] "end" "if"
+//  | "while" <expr> "do" <stmts> "end" "while"
+//  | "procedure" <ident> "(" [ <params> ] ")" "do" <stmts> "end" "procedure"
+//  | "function" <ident> "(" [ <params> ] ")" ":" <type> "do" <stmts> "end" "function"
+//  | <ident> "(" [ <args> ] ")"
+//  | "return" [ <expr> ]
 //  | "read" <var_ident>
 //  | "print" <expr>
 //  | "assert" "(" <expr> ")"
 #[derive(Clone, Debug, PartialEq)]
-pub enum Statement {
-    Declaration(String, Type, Option<Expression>),
-    Assignment(String, Expression),
-    For(String, Expression, Expression, Vec<Statement>),
-    Read(String),
+pub enum StatementKind {
+    /// The last field has the doc comments written before the declaration.
+    Declaration(Symbol, Type, Option<Expression>, Vec<Symbol>),
+    Assignment(Symbol, Expression),
+    /// The array, the index of the element and the value assigned to the element.
+    IndexedAssignment(Symbol, Expression, Expression),
+    For(Symbol, Expression, Expression, Vec<Statement>),
+    /// The condition and the statements of the then and else branches. The else branch is empty
+    /// if the statement has no ´else´.
+    If(Expression, Vec<Statement>, Vec<Statement>),
+    /// The condition, which is evaluated before every round of the loop, and the body.
+    While(Expression, Vec<Statement>),
+    /// The name, the parameters, the return type and the body of a procedure. Procedures with a
+    /// return type are functions, which are called in expressions.
+    Procedure(Symbol, Vec<Parameter>, Option<Type>, Vec<Statement>),
+    /// A call of a procedure. If a function is called, the value it returns is thrown away.
+    Call(Symbol, Vec<Expression>),
+    /// Ends the procedure being executed. Functions return the value of the expression.
+    Return(Option<Expression>),
+    Read(Symbol),
     Print(Expression),
     Assert(Expression),
 }
@@ -49,12 +107,20 @@ pub enum Statement {
 // <expr> ::= <opnd> <op> <opnd>
 //         | [ <unary_op> ] <opnd>
 // New:
-// <expr> ::= <opnd> <op> <opnd>
-//         |  <unary_op> <opnd>
-//         |  <opnd>
+// <expr> ::= <unary> ( <op> <unary> )*
+// <unary> ::= <unary_op> <unary>
+//          |  <opnd>
+// The precedence of the operators decides how the operands are grouped.
+
+#[derive(Clone, Debug)]
+pub struct Expression {
+    pub id: NodeId,
+    pub span: Span,
+    pub kind: ExpressionKind,
+}
 
 #[derive(Clone, Debug, PartialEq)]
-pub enum Expression {
+pub enum ExpressionKind {
     Binary(Operand, BinaryOperator, Operand),
     Unary(UnaryOperator, Operand),
     Singleton(Operand),
@@ -63,16 +129,80 @@ pub enum Expression {
 // <opnd> ::=
 //   <int>
 // | <string>
+// | "true" | "false"
 // | <var_ident>
+// | <var_ident> "[" <expr> "]"
+// | <ident> "(" [ <args> ] ")"
 // | "(" expr ")"
+#[derive(Clone, Debug)]
+pub struct Operand {
+    pub id: NodeId,
+    pub span: Span,
+    pub kind: OperandKind,
+}
+
 #[derive(Clone, Debug, PartialEq)]
-pub enum Operand {
+pub enum OperandKind {
     Int(BigInt),
-    StringLiteral(String),
-    Identifier(String),
+    StringLiteral(Symbol),
+    Bool(bool),
+    Identifier(Symbol),
+    /// The element of the array at the index.
+    Index(Symbol, Box<Expression>),
+    /// A call of a function with the arguments.
+    Call(Symbol, Vec<Expression>),
     Expr(Box<Expression>),
 }
 
+impl PartialEq for Statement {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+impl PartialEq for Expression {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+impl PartialEq for Operand {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+/// Nodes built by hand get a dummy ID and an empty span.
+impl From<StatementKind> for Statement {
+    fn from(kind: StatementKind) -> Self {
+        Statement {
+            id: NodeId::DUMMY,
+            span: Span::default(),
+            kind,
+        }
+    }
+}
+
+impl From<ExpressionKind> for Expression {
+    fn from(kind: ExpressionKind) -> Self {
+        Expression {
+            id: NodeId::DUMMY,
+            span: Span::default(),
+            kind,
+        }
+    }
+}
+
+impl From<OperandKind> for Operand {
+    fn from(kind: OperandKind) -> Self {
+        Operand {
+            id: NodeId::DUMMY,
+            span: Span::default(),
+            kind,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum BinaryOperator {
     Plus,
@@ -80,8 +210,51 @@ pub enum BinaryOperator {
     Multiply,
     Divide,
     LessThan,
+    GreaterThan,
+    LessThanOrEqual,
+    GreaterThanOrEqual,
     Equals,
+    NotEquals,
     And,
+    Or,
+}
+
+impl BinaryOperator {
+    /// How tightly the operator binds its operands. Operators with a higher precedence are
+    /// applied first and all of the operators are left associative.
+    /// From the loosest to the tightest: ´|´, ´&´, comparisons, ´+ -´ and ´* /´.
+    /// The unary ´!´ binds more tightly than any of these.
+    pub fn precedence(&self) -> u8 {
+        use self::BinaryOperator::*;
+        match *self {
+            Or => 1,
+            And => 2,
+            LessThan | GreaterThan | LessThanOrEqual | GreaterThanOrEqual | Equals | NotEquals => 3,
+            Plus | Minus => 4,
+            Multiply | Divide => 5,
+        }
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::BinaryOperator::*;
+        let operator = match *self {
+            Plus => "+",
+            Minus => "-",
+            Multiply => "*",
+            Divide => "/",
+            LessThan => "<",
+            GreaterThan => ">",
+            LessThanOrEqual => "<=",
+            GreaterThanOrEqual => ">=",
+            Equals => "=",
+            NotEquals => "!=",
+            And => "&",
+            Or => "|",
+        };
+        f.write_str(operator)
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -89,32 +262,282 @@ pub enum UnaryOperator {
     Not,
 }
 
-#[derive(Clone, Debug, PartialEq, Copy)]
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UnaryOperator::Not => f.write_str("!"),
+        }
+    }
+}
+
+/// The largest number of elements an array type can have.
+pub const MAX_ARRAY_SIZE: usize = 1 << 20;
+
+/// The deepest brackets, ´!´s, indices and calls can be nested inside of one expression.
+pub const MAX_NESTING: usize = 100;
+
+// <type> ::= "int" | "string" | "bool" | "array" "[" <int> "]" "of" <simple_type>
+// <simple_type> ::= "int" | "string" | "bool"
+#[derive(Clone, Debug, PartialEq)]
 pub enum Type {
     Int,
     Str,
     Bool,
+    /// The number of elements and the type of the elements, which is never an array.
+    Array(usize, Box<Type>),
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Type::Int => f.write_str("int"),
+            Type::Str => f.write_str("string"),
+            Type::Bool => f.write_str("bool"),
+            Type::Array(size, ref element) => write!(f, "array [{}] of {}", size, element),
+        }
+    }
+}
+
+/// A parameter of a procedure. The arguments of a call are assigned to the parameters, which are
+/// variables of the procedure.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parameter {
+    pub name: Symbol,
+    pub typ: Type,
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} : {}", self.name, self.typ)
+    }
 }
 
 pub struct Parser<'a, O>
 where
     O: Sink<Statement> + 'a,
 {
-    buffer: Vec<Token>,
-    for_buffer: Vec<(String, Expression, Expression, Vec<Statement>)>,
+    buffer: Vec<SpannedToken>,
+    /// The blocks whose bodies are being parsed, the innermost one last.
+    blocks: Vec<Block>,
     for_range_pointer: usize,
+    /// Doc comments waiting for the declaration they document.
+    docs: Vec<Symbol>,
+    /// Location of the token currently being parsed.
+    span: Span,
+    /// Location of the token parsed before the current one.
+    previous_span: Span,
+    /// Location of the first token of the statement currently being parsed.
+    statement_start: Span,
+    errors: &'a mut dyn Sink<ParseError>,
+    /// The number of the next ´NodeId´ to hand out.
+    next_id: u32,
     statements: &'a mut O,
 }
 
-pub fn parse<I, O>(tokens: &mut I, statements: &mut O)
+/// A statement that contains other statements.
+enum Block {
+    For(ForLoop),
+    If(IfStatement),
+    While(WhileLoop),
+    Procedure(ProcedureDefinition),
+}
+
+impl Block {
+    /// The keyword that comes after the ´end´ of the block and how it's shown in errors.
+    fn end_keyword(&self) -> (KeyWord, &'static str) {
+        match *self {
+            Block::For(_) => (KeyWord::For, "'for'"),
+            Block::If(_) => (KeyWord::If, "'if'"),
+            Block::While(_) => (KeyWord::While, "'while'"),
+            Block::Procedure(ProcedureDefinition {
+                keyword: KeyWord::Function,
+                ..
+            }) => (KeyWord::Function, "'function'"),
+            Block::Procedure(_) => (KeyWord::Procedure, "'procedure'"),
+        }
+    }
+
+    /// The statements parsed into the block so far.
+    fn body(&mut self) -> &mut Vec<Statement> {
+        match *self {
+            Block::For(ref mut for_loop) => &mut for_loop.body,
+            Block::If(IfStatement {
+                otherwise: Some(ref mut otherwise),
+                ..
+            }) => otherwise,
+            Block::If(ref mut if_statement) => &mut if_statement.then,
+            Block::While(ref mut while_loop) => &mut while_loop.body,
+            Block::Procedure(ref mut procedure) => &mut procedure.body,
+        }
+    }
+}
+
+/// A for loop whose body is being parsed.
+struct ForLoop {
+    /// The loop variable and the range. None if the loop had errors in which case the loop is
+    /// dropped at its end for. The body is still parsed so that its errors get reported.
+    header: Option<(Symbol, Expression, Expression)>,
+    body: Vec<Statement>,
+    span: Span,
+}
+
+/// A while loop whose body is being parsed.
+struct WhileLoop {
+    /// None if the condition had errors in which case the loop is dropped at its end while.
+    condition: Option<Expression>,
+    body: Vec<Statement>,
+    span: Span,
+}
+
+/// A procedure or a function whose body is being parsed.
+struct ProcedureDefinition {
+    /// ´procedure´ or ´function´, whichever started the definition.
+    keyword: KeyWord,
+    /// The name, the parameters and the return type. None if the header had errors in which case
+    /// the definition is dropped at its end.
+    header: Option<(Symbol, Vec<Parameter>, Option<Type>)>,
+    body: Vec<Statement>,
+    span: Span,
+}
+
+/// An if statement whose branches are being parsed.
+struct IfStatement {
+    /// None if the condition had errors in which case the statement is dropped at its end if.
+    condition: Option<Expression>,
+    then: Vec<Statement>,
+    /// The statements after ´else´. None until the ´else´ is found.
+    otherwise: Option<Vec<Statement>>,
+    span: Span,
+}
+
+/// The formats syntax trees can be written in and read from so that other tools can inspect and
+/// generate programs. The JSON schema is described in json.rs and the S-expressions in sexp.rs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AstFormat {
+    Json,
+    SExpression,
+}
+
+impl FromStr for AstFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(AstFormat::Json),
+            "sexp" => Ok(AstFormat::SExpression),
+            _ => Err(format!("unknown syntax tree format {}", s)),
+        }
+    }
+}
+
+/// Writes the statements in the given format.
+pub fn serialize(statements: &[Statement], format: AstFormat) -> String {
+    match format {
+        AstFormat::Json => to_json(statements).to_string(),
+        AstFormat::SExpression => to_sexp(statements),
+    }
+}
+
+/// Reads statements written in the given format. Nodes read from text get dummy IDs.
+pub fn deserialize(text: &str, format: AstFormat) -> Result<Vec<Statement>, LoadError> {
+    match format {
+        AstFormat::Json => match ::serde_json::from_str(text) {
+            Ok(value) => from_json(&value),
+            Err(why) => Err(LoadError::new(why.to_string())),
+        },
+        AstFormat::SExpression => from_sexp(text),
+    }
+}
+
+/// Checks the rules of the program structure that the parser checks while parsing on a tree that
+/// was loaded from text: procedures are only defined at the top level and ´return´ is only used
+/// inside of them.
+fn check_structure(statements: &[Statement], top_level: bool, in_procedure: bool) -> Result<(), LoadError> {
+    for statement in statements {
+        let error = |kind| Err(LoadError::new(ParseError { kind, span: statement.span }.to_string()));
+        match statement.kind {
+            StatementKind::Procedure(_, _, _, ref body) => {
+                if !top_level {
+                    return error(ParseErrorKind::NestedProcedure);
+                }
+                check_structure(body, false, true)?;
+            }
+            StatementKind::Return(_) if !in_procedure => return error(ParseErrorKind::ReturnOutsideProcedure),
+            StatementKind::For(_, _, _, ref body) | StatementKind::While(_, ref body) => {
+                check_structure(body, false, in_procedure)?;
+            }
+            StatementKind::If(_, ref then, ref otherwise) => {
+                check_structure(then, false, in_procedure)?;
+                check_structure(otherwise, false, in_procedure)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Parses statements from the tokens and puts them into ´statements´ as they are completed.
+/// A statement with a syntax error is skipped and parsing continues from the next statement, so
+/// the returned list contains every syntax error in the input.
+pub fn parse<I, O>(tokens: &mut I, statements: &mut O) -> Vec<ParseError>
+where
+    I: Source<SpannedToken>,
+    O: Sink<Statement>,
+{
+    let mut errors = Vec::new();
+    parse_reporting(tokens, statements, &mut errors);
+    errors
+}
+
+/// Like ´parse´ but puts the syntax errors into ´errors´ as soon as they are found, so that they
+/// can be seen while the statements before them are still being used.
+pub fn parse_reporting<I, O, E>(tokens: &mut I, statements: &mut O, errors: &mut E)
 where
-    I: Source<Token>,
+    I: Source<SpannedToken>,
     O: Sink<Statement>,
+    E: Sink<ParseError>,
 {
-    let mut parser = Parser::new(statements);
+    let mut parser = Parser::new(statements, errors);
     let mut state = State(Parser::normal_parse);
+    // Statements end in ´;´ and the headers of blocks in ´do´, ´then´ or ´else´. Anything else at
+    // the end of the input is an unfinished statement.
+    let mut between_statements = true;
     while let Some(t) = tokens.take() {
-        state = state(&mut parser, t);
+        if t.token == Token::EndOfInput {
+            break;
+        }
+        // Doc comments are collected here instead of in the states. They document the statement
+        // after them, so they can only be in between statements.
+        if let Token::DocComment(doc) = t.token {
+            if between_statements {
+                parser.docs.push(doc);
+            } else {
+                parser.error(ParseErrorKind::MisplacedDocComment, t.span);
+            }
+            continue;
+        }
+        between_statements = matches!(
+            t.token,
+            Token::Semicolon
+                | Token::KeyWord(KeyWord::Do)
+                | Token::KeyWord(KeyWord::Then)
+                | Token::KeyWord(KeyWord::Else)
+        );
+        parser.previous_span = parser.span;
+        parser.span = t.span;
+        state = state(&mut parser, t.token);
+    }
+    if !between_statements {
+        let span = parser.span;
+        parser.error(ParseErrorKind::UnexpectedEnd("';'"), span);
+    }
+    for block in mem::take(&mut parser.blocks) {
+        match block {
+            Block::For(for_loop) => parser.error(ParseErrorKind::UnclosedFor, for_loop.span),
+            Block::If(if_statement) => parser.error(ParseErrorKind::UnclosedIf, if_statement.span),
+            Block::While(while_loop) => parser.error(ParseErrorKind::UnclosedWhile, while_loop.span),
+            Block::Procedure(procedure) => parser.error(ParseErrorKind::UnclosedProcedure, procedure.span),
+        }
     }
 }
 
@@ -122,56 +545,87 @@ where
 //  <stmts> ::= <stmt> ";" ( <stmt> ";" )*
 //  <stmt> ::= "var" <var_ident> ":" <type> [ ":=" <expr> ]
 //  | <var_ident> ":=" <expr>
+//  | <var_ident> "[" <expr> "]" ":=" <expr>
 //  | "for" <var_ident> "in" <expr> ".." <expr> "do"
 //  <stmts> "end" "for"
+//  | "if" <expr> "then" <stmts> [ "else" <stmts> ] "end" "if"
+//  | "while" <expr> "do" <stmts> "end" "while"
+//  | "procedure" <ident> "(" [ <params> ] ")" "do" <stmts> "end" "procedure"
+//  | "function" <ident> "(" [ <params> ] ")" ":" <type> "do" <stmts> "end" "function"
+//  | <ident> "(" [ <args> ] ")"
+//  | "return" [ <expr> ]
 //  | "read" <var_ident>
 //  | "print" <expr>
 //  | "assert" "(" <expr> ")"
-//  <expr> ::= <opnd> <op> <opnd>
-//  | [ <unary_op> ] <opnd>
+//  <params> ::= <var_ident> ":" <type> ( "," <var_ident> ":" <type> )*
+//  <args> ::= <expr> ( "," <expr> )*
+//  <expr> ::= <unary> ( <op> <unary> )*
+//  <unary> ::= <unary_op> <unary> | <opnd>
 //  <opnd> ::= <int>
 //  | <string>
+//  | "true" | "false"
 //  | <var_ident>
+//  | <var_ident> "[" <expr> "]"
+//  | <ident> "(" [ <args> ] ")"
 //  | "(" expr ")"
-//  <type> ::= "int" | "string" | "bool"
+//  <type> ::= "int" | "string" | "bool" | "array" "[" <int> "]" "of" <simple_type>
+//  <simple_type> ::= "int" | "string" | "bool"
 //  <var_ident> ::= <ident>
 //  <reserved keyword> ::=
 //  "var" | "for" | "end" | "in" | "do" | "read" |
 //  "print" | "int" | "string" | "bool" | "assert"
+//  and in the extended dialect "if" | "then" | "else" | "while" | "true" | "false" |
+//  "procedure" | "function" | "return" | "array" | "of"
 // I tried the design pattern described here
 // https://dev.to/mindflavor/lets-build-zork-using-rust-1opm
 impl<'a, O> Parser<'a, O>
 where
     O: Sink<Statement>,
 {
-    fn new(statements: &'a mut O) -> Self {
+    fn new(statements: &'a mut O, errors: &'a mut dyn Sink<ParseError>) -> Self {
         Parser {
             buffer: Vec::new(),
-            for_buffer: Vec::new(),
+            blocks: Vec::new(),
             for_range_pointer: 0,
+            docs: Vec::new(),
+            span: Span::default(),
+            previous_span: Span::default(),
+            statement_start: Span::default(),
+            errors,
+            next_id: 0,
             statements,
         }
     }
 
     fn normal_parse(&mut self, t: Token) -> State<'a, O> {
+        self.statement_start = self.span;
+        // Only declarations can be documented.
+        if t != Token::KeyWord(KeyWord::Var) {
+            self.docs.clear();
+        }
         match t {
             Token::Identifier(_) => {
-                self.buffer.push(t);
+                self.push(t);
                 State(Self::assignment_parse)
             }
-            Token::KeyWord(keyword) => match keyword {
-                KeyWord::Var => State(Self::variable_definition_parse),
-                KeyWord::For => State(Self::for_loop_parse),
-                KeyWord::Read => State(Self::read_parse),
-                KeyWord::Print => State(Self::print_parse),
-                KeyWord::Assert => State(Self::assert_parse),
-                KeyWord::End => State(Self::expect_end_for),
-                _ => panic!("a statement cannot start with the keyword {:#?}", keyword),
-            },
+            Token::KeyWord(KeyWord::Var) => State(Self::variable_definition_parse),
+            Token::KeyWord(KeyWord::For) => State(Self::for_loop_parse),
+            Token::KeyWord(KeyWord::If) => State(Self::if_parse),
+            Token::KeyWord(KeyWord::Else) => self.else_parse(),
+            Token::KeyWord(KeyWord::While) => State(Self::while_parse),
+            Token::KeyWord(KeyWord::Procedure) | Token::KeyWord(KeyWord::Function) => {
+                self.push(t);
+                State(Self::procedure_parse)
+            }
+            Token::KeyWord(KeyWord::Return) => State(Self::return_parse),
+            Token::KeyWord(KeyWord::Read) => State(Self::read_parse),
+            Token::KeyWord(KeyWord::Print) => State(Self::print_parse),
+            Token::KeyWord(KeyWord::Assert) => State(Self::assert_parse),
+            Token::KeyWord(KeyWord::End) => State(Self::expect_end),
             //empty statements are allowed. They are skiped.
             Token::Semicolon => State(Self::normal_parse),
 
-            _ => panic!("unexpected token: {:#?}", t),
+            _ => self.expected("a statement", t),
         }
     }
 
@@ -179,80 +633,110 @@ where
     fn variable_definition_parse(&mut self, t: Token) -> State<'a, O> {
         match self.buffer.len() {
             0 => match t {
-                Token::Identifier(_) => self.buffer.push(t),
-                _ => panic!("Expected an identifier but found {:#?} instead", t),
+                Token::Identifier(_) => self.push(t),
+                _ => return self.expected("an identifier", t),
             },
             1 => match t {
-                Token::Colon => self.buffer.push(t),
-                _ => panic!("Expected a colon but found {:#?} instead", t),
+                Token::Colon => self.push(t),
+                _ => return self.expected("':'", t),
             },
             2 => match t {
                 Token::KeyWord(KeyWord::String)
                 | Token::KeyWord(KeyWord::Int)
-                | Token::KeyWord(KeyWord::Bool) => self.buffer.push(t),
-                _ => panic!("Expected a type signature but found {:#?} instead", t),
+                | Token::KeyWord(KeyWord::Bool)
+                | Token::KeyWord(KeyWord::Array) => self.push(t),
+                _ => return self.expected("a type", t),
             },
             n => match t {
                 Token::Semicolon => {
-                    let identifier = match self.buffer[0] {
-                        Token::Identifier(ref i) => i.clone(),
+                    let identifier = match self.buffer[0].token {
+                        Token::Identifier(i) => i,
                         _ => unreachable!(),
                     };
-                    let typ = match self.buffer[2] {
-                        Token::KeyWord(KeyWord::String) => Type::Str,
-                        Token::KeyWord(KeyWord::Int) => Type::Int,
-                        Token::KeyWord(KeyWord::Bool) => Type::Bool,
-                        _ => unreachable!(),
+                    // The type ends at the ´:=´ if there is one.
+                    let type_end = self.buffer[3..]
+                        .iter()
+                        .position(|t| t.token == Token::Assignment)
+                        .map_or(n, |i| i + 3);
+                    let typ = {
+                        let (end, end_span) = match self.buffer.get(type_end) {
+                            Some(assignment) => (assignment.token.clone(), assignment.span),
+                            None => (t.clone(), self.span),
+                        };
+                        let mut tokens = self.buffer[2..type_end].iter();
+                        parse_type(&mut tokens, &end, end_span).and_then(|typ| match tokens.next() {
+                            Some(found) => Err(expected_error("':=' or ';'", Some(found), &end, end_span)),
+                            None => Ok(typ),
+                        })
                     };
-                    let expr = match n {
-                        3 => None,
-                        4 => panic!("Var statement ended prematurely."),
-                        n if n > 4 => Some(parse_expression(&self.buffer[4..])),
-                        _ => unreachable!(),
+                    let typ = match typ {
+                        Ok(typ) => typ,
+                        Err(error) => {
+                            self.errors.put(error);
+                            self.buffer.clear();
+                            return State(Self::normal_parse);
+                        }
+                    };
+                    let expr = if type_end == n {
+                        None
+                    } else {
+                        match self.buffered_expression(type_end + 1, n, &t) {
+                            Some(expr) => Some(expr),
+                            None => return State(Self::normal_parse),
+                        }
                     };
-                    self.handle_statement(Statement::Declaration(identifier, typ, expr));
+                    let docs = mem::take(&mut self.docs);
+                    let end = self.previous_span;
+                    let kind = StatementKind::Declaration(identifier, typ, expr, docs);
+                    self.handle_statement(kind, end);
                     return State(Self::normal_parse);
                 }
-                Token::Assignment => match n {
-                    3 => self.buffer.push(t),
-                    _ => panic!("an assignment is not valid in an expression"),
-                },
-                _ => self.buffer.push(t),
+                Token::Assignment if n == 3 => self.push(t),
+                // Only array types are longer than one token.
+                _ if n == 3 && self.buffer[2].token != Token::KeyWord(KeyWord::Array) => {
+                    return self.expected("':=' or ';'", t)
+                }
+                _ => self.push(t),
             },
         }
         State(Self::variable_definition_parse)
     }
 
     fn assignment_parse(&mut self, t: Token) -> State<'a, O> {
-        //let len = self.buffer.len();
         if self.buffer.len() == 1 {
             match t {
-                Token::Assignment => self.buffer.push(t),
-                _ => panic!("expected a := but found {:#?} instead", t),
+                Token::Assignment => self.push(t),
+                Token::Bracket(Direction::Left) => {
+                    self.push(t);
+                    return State(Self::call_parse);
+                }
+                Token::SquareBracket(Direction::Left) => {
+                    self.push(t);
+                    return State(Self::indexed_assignment_parse);
+                }
+                _ => return self.expected("':=', '[' or '('", t),
             }
             State(Self::assignment_parse)
         } else {
             match t {
                 Token::Semicolon => {
-                    let statement = match &self.buffer[0] {
-                        &Token::Identifier(ref identifier) => {
-                            Statement::Assignment(
-                                    identifier.clone(),
-                                    parse_expression(&self.buffer[2..])
-                            )
-                        },
+                    let identifier = match self.buffer[0].token {
+                        Token::Identifier(identifier) => identifier,
                         _ => unreachable!(
                             "the first token of the buffer during assignment parsing was something other than an identifier"
                         ),
                     };
-                    self.handle_statement(statement);
+                    let len = self.buffer.len();
+                    if let Some(expression) = self.buffered_expression(2, len, &t) {
+                        let end = self.previous_span;
+                        self.handle_statement(StatementKind::Assignment(identifier, expression), end);
+                    }
                     State(Self::normal_parse)
                 }
                 _ => {
-                    self.buffer.push(t);
+                    self.push(t);
                     State(Self::assignment_parse)
                 }
-                _ => panic!("unexpected Token {:#?} read during", t),
             }
         }
     }
@@ -261,88 +745,402 @@ where
     fn for_loop_parse(&mut self, t: Token) -> State<'a, O> {
         match self.buffer.len() {
             0 => match t {
-                Token::Identifier(_) => self.buffer.push(t),
-                _ => panic!("Expected an identifier, found {:#?}", t),
+                Token::Identifier(_) => self.push(t),
+                _ => return self.for_header_error("an identifier", t),
             },
             1 => match t {
-                Token::KeyWord(KeyWord::In) => self.buffer.push(t),
-                _ => panic!("Expected keyword 'in', found {:#?}"),
+                Token::KeyWord(KeyWord::In) => self.push(t),
+                _ => return self.for_header_error("'in'", t),
             },
             _ => match t {
                 Token::KeyWord(KeyWord::Do) => {
-                    if self.for_range_pointer < 3 {
-                        panic!("incorrect for loop range");
+                    if self.for_range_pointer == 0 {
+                        return self.for_header_error("'..'", t);
                     }
-                    let identifier = match self.buffer[0] {
-                        Token::Identifier(ref i) => i.clone(),
+                    let identifier = match self.buffer[0].token {
+                        Token::Identifier(i) => i,
                         _ => unreachable!("the buffer did not have an identifier as the first element when parsing a for loop"),
                     };
-                    self.for_buffer.push((
-                        identifier,
-                        parse_expression(&self.buffer[2..self.for_range_pointer]),
-                        parse_expression(
-                            &self.buffer[(self.for_range_pointer + 1)..self.buffer.len()],
-                        ),
-                        Vec::new(),
-                    ));
-                    self.for_range_pointer = 0;
-                    self.buffer.clear();
+                    let range = self.for_range_pointer;
+                    let range_token = self.buffer[range].token.clone();
+                    let len = self.buffer.len();
+                    let from = self.buffered_expression(2, range, &range_token);
+                    let to = self.buffered_expression(range + 1, len, &t);
+                    let header = match (from, to) {
+                        (Some(from), Some(to)) => Some((identifier, from, to)),
+                        _ => None,
+                    };
+                    self.start_for_body(header);
                     return State(Self::normal_parse);
                 }
                 Token::Range => {
                     if self.for_range_pointer == 0 {
                         self.for_range_pointer = self.buffer.len();
-                        self.buffer.push(t);
+                        self.push(t);
                     } else {
-                        panic!("found more than one range during for loop parsing");
+                        return self.for_header_error("'do'", t);
                     }
                 }
+                Token::Semicolon => return self.for_header_error("'do'", t),
                 _ => {
-                    self.buffer.push(t);
+                    self.push(t);
                 }
             },
         }
         State(Self::for_loop_parse)
     }
 
-    fn expect_end_for(&mut self, t: Token) -> State<'a, O> {
+    /// Reports an error in the header of a for loop. The body of the loop is still parsed so that
+    /// its ´end for´ is not mistaken for the end of an outer loop.
+    fn for_header_error(&mut self, expected: &'static str, t: Token) -> State<'a, O> {
+        let span = self.span;
+        self.error(ParseErrorKind::Expected(expected, t.clone()), span);
+        self.skip_for_header(t)
+    }
+
+    fn skip_for_header(&mut self, t: Token) -> State<'a, O> {
+        match t {
+            Token::KeyWord(KeyWord::Do) | Token::Semicolon => {
+                self.start_for_body(None);
+                State(Self::normal_parse)
+            }
+            _ => State(Self::skip_for_header),
+        }
+    }
+
+    fn start_for_body(&mut self, header: Option<(Symbol, Expression, Expression)>) {
+        self.blocks.push(Block::For(ForLoop {
+            header,
+            body: Vec::new(),
+            span: self.statement_start,
+        }));
+        self.for_range_pointer = 0;
+        self.buffer.clear();
+    }
+
+    // "if" <expr> "then"
+    fn if_parse(&mut self, t: Token) -> State<'a, O> {
         match t {
-            Token::KeyWord(KeyWord::For) => {
-                let (identifier, from, to, statements) = self.for_buffer
-                    .pop()
-                    .expect("encountered an end for but no for loops were initialized.");
+            Token::KeyWord(KeyWord::Then) => {
+                let len = self.buffer.len();
+                let condition = self.buffered_expression(0, len, &t);
+                self.start_if(condition);
+                State(Self::normal_parse)
+            }
+            // The branches are still parsed so that the end if doesn't end an outer block.
+            Token::Semicolon => {
+                let span = self.span;
+                self.error(ParseErrorKind::Expected("'then'", t), span);
+                self.start_if(None);
+                State(Self::normal_parse)
+            }
+            _ => {
+                self.push(t);
+                State(Self::if_parse)
+            }
+        }
+    }
 
-                let for_statement = Statement::For(identifier, from, to, statements);
+    fn start_if(&mut self, condition: Option<Expression>) {
+        self.blocks.push(Block::If(IfStatement {
+            condition,
+            then: Vec::new(),
+            otherwise: None,
+            span: self.statement_start,
+        }));
+        self.buffer.clear();
+    }
+
+    // "else" <stmts>
+    fn else_parse(&mut self) -> State<'a, O> {
+        match self.blocks.last_mut() {
+            Some(&mut Block::If(ref mut if_statement)) if if_statement.otherwise.is_none() => {
+                if_statement.otherwise = Some(Vec::new());
+            }
+            _ => {
+                let span = self.span;
+                self.error(ParseErrorKind::UnmatchedElse, span);
+            }
+        }
+        State(Self::normal_parse)
+    }
 
-                self.handle_statement(for_statement);
+    // "while" <expr> "do"
+    fn while_parse(&mut self, t: Token) -> State<'a, O> {
+        match t {
+            Token::KeyWord(KeyWord::Do) => {
+                let len = self.buffer.len();
+                let condition = self.buffered_expression(0, len, &t);
+                self.start_while(condition);
+                State(Self::normal_parse)
             }
-            _ => panic!("Expected end after for, found {:#?} instead", t),
+            // The body is still parsed so that the end while doesn't end an outer block.
+            Token::Semicolon => {
+                let span = self.span;
+                self.error(ParseErrorKind::Expected("'do'", t), span);
+                self.start_while(None);
+                State(Self::normal_parse)
+            }
+            _ => {
+                self.push(t);
+                State(Self::while_parse)
+            }
+        }
+    }
+
+    fn start_while(&mut self, condition: Option<Expression>) {
+        self.blocks.push(Block::While(WhileLoop {
+            condition,
+            body: Vec::new(),
+            span: self.statement_start,
+        }));
+        self.buffer.clear();
+    }
+
+    // <var_ident> "[" <expr> "]" ":=" <expr>
+    fn indexed_assignment_parse(&mut self, t: Token) -> State<'a, O> {
+        match t {
+            Token::Semicolon => {
+                let identifier = match self.buffer[0].token {
+                    Token::Identifier(identifier) => identifier,
+                    _ => unreachable!("the indexed assignment did not start with an identifier"),
+                };
+                // The index can't contain a ´:=´ so the first one ends the index.
+                let assignment = match self.buffer.iter().position(|t| t.token == Token::Assignment) {
+                    Some(assignment) => assignment,
+                    None => return self.expected("':='", t),
+                };
+                let bracket = self.buffer[assignment - 1].token.clone();
+                if bracket != Token::SquareBracket(Direction::Right) {
+                    let (found, span) = (self.buffer[assignment].token.clone(), self.buffer[assignment].span);
+                    self.error(ParseErrorKind::Expected("']'", found), span);
+                    self.buffer.clear();
+                    return State(Self::normal_parse);
+                }
+                let len = self.buffer.len();
+                let index = match self.buffered_expression(2, assignment - 1, &bracket) {
+                    Some(index) => index,
+                    None => return State(Self::normal_parse),
+                };
+                if let Some(value) = self.buffered_expression(assignment + 1, len, &t) {
+                    let end = self.previous_span;
+                    self.handle_statement(StatementKind::IndexedAssignment(identifier, index, value), end);
+                }
+                State(Self::normal_parse)
+            }
+            _ => {
+                self.push(t);
+                State(Self::indexed_assignment_parse)
+            }
+        }
+    }
+
+    // <ident> "(" [ <args> ] ")"
+    fn call_parse(&mut self, t: Token) -> State<'a, O> {
+        match t {
+            Token::Semicolon => {
+                let result = parse_call(&self.buffer, &t, self.span, &mut self.next_id);
+                match result {
+                    Ok((name, arguments)) => {
+                        let end = self.previous_span;
+                        self.handle_statement(StatementKind::Call(name, arguments), end);
+                    }
+                    Err(error) => {
+                        self.errors.put(error);
+                        self.buffer.clear();
+                    }
+                }
+                State(Self::normal_parse)
+            }
+            _ => {
+                self.push(t);
+                State(Self::call_parse)
+            }
+        }
+    }
+
+    // "procedure" <ident> "(" [ <params> ] ")" "do"
+    // "function" <ident> "(" [ <params> ] ")" ":" <type> "do"
+    fn procedure_parse(&mut self, t: Token) -> State<'a, O> {
+        match t {
+            Token::KeyWord(KeyWord::Do) => {
+                let header = match parse_signature(&self.buffer, &t, self.span) {
+                    Ok(header) => Some(header),
+                    Err(error) => {
+                        self.errors.put(error);
+                        None
+                    }
+                };
+                self.start_procedure(header);
+                State(Self::normal_parse)
+            }
+            // The body is still parsed so that its end doesn't end an outer block.
+            Token::Semicolon => {
+                let span = self.span;
+                self.error(ParseErrorKind::Expected("'do'", t), span);
+                self.start_procedure(None);
+                State(Self::normal_parse)
+            }
+            _ => {
+                self.push(t);
+                State(Self::procedure_parse)
+            }
+        }
+    }
+
+    fn start_procedure(&mut self, mut header: Option<(Symbol, Vec<Parameter>, Option<Type>)>) {
+        let keyword = match self.buffer[0].token {
+            Token::KeyWord(ref keyword) => keyword.clone(),
+            _ => unreachable!("the buffer did not start with the keyword of the procedure"),
+        };
+        // The interpreter has one table of procedures for the whole program so procedures can't
+        // be local to a block.
+        if !self.blocks.is_empty() {
+            let span = self.statement_start;
+            self.error(ParseErrorKind::NestedProcedure, span);
+            header = None;
+        }
+        self.blocks.push(Block::Procedure(ProcedureDefinition {
+            keyword,
+            header,
+            body: Vec::new(),
+            span: self.statement_start,
+        }));
+        self.buffer.clear();
+    }
+
+    // "return" [ <expr> ]
+    fn return_parse(&mut self, t: Token) -> State<'a, O> {
+        match t {
+            Token::Semicolon => {
+                let in_procedure = self.blocks.iter().any(|block| matches!(*block, Block::Procedure(_)));
+                if !in_procedure {
+                    let span = self.statement_start;
+                    self.error(ParseErrorKind::ReturnOutsideProcedure, span);
+                    self.buffer.clear();
+                    return State(Self::normal_parse);
+                }
+                let len = self.buffer.len();
+                let value = if len == 0 {
+                    None
+                } else {
+                    match self.buffered_expression(0, len, &t) {
+                        Some(value) => Some(value),
+                        None => return State(Self::normal_parse),
+                    }
+                };
+                let end = self.previous_span;
+                self.handle_statement(StatementKind::Return(value), end);
+                State(Self::normal_parse)
+            }
+            _ => {
+                self.push(t);
+                State(Self::return_parse)
+            }
+        }
+    }
+
+    // "end" "for" | "end" "if" | "end" "while" | "end" "procedure" | "end" "function"
+    fn expect_end(&mut self, t: Token) -> State<'a, O> {
+        let block = match t {
+            Token::KeyWord(KeyWord::For)
+            | Token::KeyWord(KeyWord::If)
+            | Token::KeyWord(KeyWord::While)
+            | Token::KeyWord(KeyWord::Procedure)
+            | Token::KeyWord(KeyWord::Function) => self.blocks.pop(),
+            _ => return self.expected("'for', 'if', 'while', 'procedure' or 'function'", t),
         };
+        let end = self.span;
+        // The statements in the bodies have moved the start of the statement.
+        match (t, block) {
+            (
+                Token::KeyWord(KeyWord::For),
+                Some(Block::For(ForLoop {
+                    header: Some((identifier, from, to)),
+                    body,
+                    span,
+                })),
+            ) => {
+                self.statement_start = span;
+                self.handle_statement(StatementKind::For(identifier, from, to, body), end);
+            }
+            (
+                Token::KeyWord(KeyWord::If),
+                Some(Block::If(IfStatement {
+                    condition: Some(condition),
+                    then,
+                    otherwise,
+                    span,
+                })),
+            ) => {
+                self.statement_start = span;
+                let kind = StatementKind::If(condition, then, otherwise.unwrap_or_default());
+                self.handle_statement(kind, end);
+            }
+            (
+                Token::KeyWord(KeyWord::While),
+                Some(Block::While(WhileLoop {
+                    condition: Some(condition),
+                    body,
+                    span,
+                })),
+            ) => {
+                self.statement_start = span;
+                self.handle_statement(StatementKind::While(condition, body), end);
+            }
+            (
+                t,
+                Some(Block::Procedure(ProcedureDefinition {
+                    keyword,
+                    header: Some((name, parameters, return_type)),
+                    body,
+                    span,
+                })),
+            ) if t == Token::KeyWord(keyword.clone()) =>
+            {
+                self.statement_start = span;
+                let kind = StatementKind::Procedure(name, parameters, return_type, body);
+                self.handle_statement(kind, end);
+            }
+            // The innermost block is dropped when it's ended with the wrong keyword.
+            (t, Some(block)) => {
+                let (keyword, expected) = block.end_keyword();
+                if t != Token::KeyWord(keyword) {
+                    self.error(ParseErrorKind::Expected(expected, t), end);
+                }
+            }
+            (_, None) => {
+                let span = self.statement_start;
+                self.error(ParseErrorKind::UnmatchedEnd, span);
+            }
+        }
         State(Self::expect_semicolon)
     }
 
     // "read" <var_ident>
     fn read_parse(&mut self, t: Token) -> State<'a, O> {
         match t {
-            Token::Identifier(i) => self.handle_statement(Statement::Read(i)),
-            _ => panic!("expected an identifier after a read"),
+            Token::Identifier(i) => {
+                let end = self.span;
+                self.handle_statement(StatementKind::Read(i), end);
+            }
+            _ => return self.expected("an identifier", t),
         };
-        State(Self::normal_parse)
+        State(Self::expect_semicolon)
     }
 
     // "print" <expr>
     fn print_parse(&mut self, t: Token) -> State<'a, O> {
         match t {
             Token::Semicolon => {
-                if 0 < self.buffer.len() {
-                    let expression = parse_expression(&self.buffer);
-                    self.handle_statement(Statement::Print(expression));
-                    return State(Self::normal_parse);
-                } else {
-                    panic!("expected an expression after print.")
+                let len = self.buffer.len();
+                if let Some(expression) = self.buffered_expression(0, len, &t) {
+                    let end = self.previous_span;
+                    self.handle_statement(StatementKind::Print(expression), end);
                 }
+                return State(Self::normal_parse);
             }
-            _ => self.buffer.push(t),
+            _ => self.push(t),
         }
         State(Self::print_parse)
     }
@@ -351,189 +1149,547 @@ where
     fn assert_parse(&mut self, t: Token) -> State<'a, O> {
         match self.buffer.len() {
             0 => match t {
-                Token::Bracket(Direction::Left) => self.buffer.push(t),
-                _ => panic!(
-                    "expected a left bracket after assert but found {:#?} instead",
-                    t
-                ),
+                Token::Bracket(Direction::Left) => self.push(t),
+                _ => return self.expected("'('", t),
             },
-            _ => match t {
-                Token::Semicolon => match self.buffer.pop().unwrap() {
-                    Token::Bracket(Direction::Right) => {
-                        if self.buffer.len() > 1 {
-                            let expression = parse_expression(&self.buffer[1..]);
-                            self.handle_statement(Statement::Assert(expression));
-                            return State(Self::normal_parse);
-                        } else {
-                            panic!("invalid expression.");
+            len => match t {
+                Token::Semicolon => {
+                    match self.buffer[len - 1].token {
+                        Token::Bracket(Direction::Right) if len > 1 => {
+                            let bracket = self.buffer[len - 1].token.clone();
+                            if let Some(expression) = self.buffered_expression(1, len - 1, &bracket)
+                            {
+                                let end = self.previous_span;
+                                self.handle_statement(StatementKind::Assert(expression), end);
+                            }
+                        }
+                        _ => {
+                            let span = self.span;
+                            self.error(ParseErrorKind::Expected("')'", t), span);
+                            self.buffer.clear();
                         }
                     }
-                    _ => panic!(
-                        "expected a right parenthesis to end the expression in an assert statement"
-                    ),
-                },
-                _ => self.buffer.push(t),
+                    return State(Self::normal_parse);
+                }
+                _ => self.push(t),
             },
         }
         State(Self::assert_parse)
     }
 
-    // <expr> ::= <opnd> <op> <opnd>
-    //         |  <unary_op> <opnd>
-    //         |  <opnd>
-    //  <opnd> ::= <int>
-    //  | <string>
-    //  | <var_ident>
-    //  | "(" expr ")"
-
-    fn handle_statement(&mut self, statement: Statement) {
-        if self.for_buffer.is_empty() {
-            self.statements.put(statement);
-        } else {
-            let len = self.for_buffer.len() - 1;
-            self.for_buffer[len].3.push(statement);
+    /// Finishes a statement that started at ´statement_start´ and ended with the token at ´end´.
+    fn handle_statement(&mut self, kind: StatementKind, end: Span) {
+        let statement = Statement {
+            id: NodeId(self.next_id),
+            span: self.statement_start.to(end),
+            kind,
+        };
+        self.next_id += 1;
+        match self.blocks.last_mut() {
+            Some(block) => block.body().push(statement),
+            None => self.statements.put(statement),
         }
         self.buffer.clear();
+        self.docs.clear();
     }
 
     fn expect_semicolon(&mut self, t: Token) -> State<'a, O> {
         match t {
             Token::Semicolon => State(Self::normal_parse),
-            _ => panic!("expected a semicolon, found {:#?} instead", t),
+            _ => self.expected("';'", t),
+        }
+    }
+
+    fn push(&mut self, token: Token) {
+        self.buffer.push(SpannedToken {
+            token,
+            span: self.span,
+            leading_trivia: Vec::new(),
+            text: String::new(),
+        });
+    }
+
+    /// Parses the buffered tokens ´start..end´ as an expression that ended at ´end_token´.
+    /// If the expression has errors, they are reported and the statement is dropped.
+    fn buffered_expression(&mut self, start: usize, end: usize, end_token: &Token) -> Option<Expression> {
+        let result = parse_expression(
+            &self.buffer[start..end],
+            end_token,
+            self.span,
+            &mut self.next_id,
+        );
+        match result {
+            Ok(expression) => Some(expression),
+            Err(error) => {
+                self.errors.put(error);
+                self.buffer.clear();
+                None
+            }
+        }
+    }
+
+    fn error(&mut self, kind: ParseErrorKind, span: Span) {
+        self.errors.put(ParseError { kind, span });
+    }
+
+    /// Reports that ´t´ was found where something else was expected and skips the rest of the
+    /// statement.
+    fn expected(&mut self, expected: &'static str, t: Token) -> State<'a, O> {
+        let span = self.span;
+        self.error(ParseErrorKind::Expected(expected, t.clone()), span);
+        self.buffer.clear();
+        self.for_range_pointer = 0;
+        self.skip_statement(t)
+    }
+
+    /// Skips tokens until the end of the statement. ´end´ and ´else´ are taken as the start of an
+    /// ´end for´, ´end if´ or else branch so that the block the statement was in still ends where
+    /// it should.
+    fn skip_statement(&mut self, t: Token) -> State<'a, O> {
+        match t {
+            Token::Semicolon => State(Self::normal_parse),
+            Token::KeyWord(KeyWord::End) => {
+                self.statement_start = self.span;
+                State(Self::expect_end)
+            }
+            Token::KeyWord(KeyWord::Else) => self.else_parse(),
+            _ => State(Self::skip_statement),
+        }
+    }
+}
+
+/// Parses a whole expression that ended at ´end´, which is found at ´end_span´. Binary operators
+/// are left associative and bind according to ´BinaryOperator::precedence´.
+fn parse_expression(
+    tokens: &[SpannedToken],
+    end: &Token,
+    end_span: Span,
+    next_id: &mut u32,
+) -> Result<Expression, ParseError> {
+    let mut parser = ExpressionParser {
+        tokens,
+        position: 0,
+        end,
+        end_span,
+        next_id,
+        depth: 0,
+    };
+    let expression = parser.expression(0)?;
+    match parser.next() {
+        Some(t) => Err(parser.expected("an operator", Some(t))),
+        None => Ok(expression),
+    }
+}
+
+/// Parses a type from the start of ´tokens´, which are followed by ´end´ at ´end_span´.
+fn parse_type(tokens: &mut slice::Iter<SpannedToken>, end: &Token, end_span: Span) -> Result<Type, ParseError> {
+    let expected = |expected: &'static str, found: Option<&SpannedToken>| {
+        expected_error(expected, found, end, end_span)
+    };
+    let expect = |found: Option<&SpannedToken>, token: Token, description| match found {
+        Some(t) if t.token == token => Ok(()),
+        _ => Err(expected(description, found)),
+    };
+    let found = tokens.next();
+    if found.map(|t| &t.token) != Some(&Token::KeyWord(KeyWord::Array)) {
+        return found.and_then(|t| match_type(&t.token)).ok_or_else(|| expected("a type", found));
+    }
+    expect(tokens.next(), Token::SquareBracket(Direction::Left), "'['")?;
+    let found = tokens.next();
+    let size = match found.map(|t| &t.token) {
+        Some(Token::Number(n)) => n.to_usize(),
+        _ => None,
+    };
+    let size = size.ok_or_else(|| expected("an array size", found))?;
+    if size > MAX_ARRAY_SIZE {
+        return Err(ParseError {
+            kind: ParseErrorKind::ArrayTooLarge,
+            span: found.map_or(end_span, |t| t.span),
+        });
+    }
+    expect(tokens.next(), Token::SquareBracket(Direction::Right), "']'")?;
+    expect(tokens.next(), Token::KeyWord(KeyWord::Of), "'of'")?;
+    let found = tokens.next();
+    let element = found
+        .and_then(|t| match_type(&t.token))
+        .ok_or_else(|| expected("'int', 'string' or 'bool'", found))?;
+    Ok(Type::Array(size, Box::new(element)))
+}
+
+/// An error for finding ´found´ when ´expected´ was expected. If the tokens ran out, ´end´ at
+/// ´end_span´ was found instead.
+fn expected_error(expected: &'static str, found: Option<&SpannedToken>, end: &Token, end_span: Span) -> ParseError {
+    match found {
+        Some(t) => ParseError {
+            kind: ParseErrorKind::Expected(expected, t.token.clone()),
+            span: t.span,
+        },
+        None => ParseError {
+            kind: ParseErrorKind::Expected(expected, end.clone()),
+            span: end_span,
+        },
+    }
+}
+
+/// Parses the tokens of a call statement, which ended at ´end´.
+fn parse_call(
+    tokens: &[SpannedToken],
+    end: &Token,
+    end_span: Span,
+    next_id: &mut u32,
+) -> Result<(Symbol, Vec<Expression>), ParseError> {
+    let name = match tokens[0].token {
+        Token::Identifier(name) => name,
+        _ => unreachable!("the call did not start with an identifier"),
+    };
+    let mut parser = ExpressionParser {
+        tokens,
+        position: 1,
+        end,
+        end_span,
+        next_id,
+        depth: 0,
+    };
+    let (arguments, _) = parser.arguments()?;
+    match parser.next() {
+        Some(t) => Err(parser.expected("';'", Some(t))),
+        None => Ok((name, arguments)),
+    }
+}
+
+/// Parses the header of a procedure from the tokens from ´procedure´ or ´function´ up to ´end´,
+/// the ´do´ of the header.
+fn parse_signature(
+    tokens: &[SpannedToken],
+    end: &Token,
+    end_span: Span,
+) -> Result<(Symbol, Vec<Parameter>, Option<Type>), ParseError> {
+    let expected = |expected: &'static str, found: Option<&SpannedToken>| {
+        expected_error(expected, found, end, end_span)
+    };
+    let function = tokens[0].token == Token::KeyWord(KeyWord::Function);
+    let mut tokens = tokens[1..].iter();
+    let name = match tokens.next() {
+        Some(&SpannedToken {
+            token: Token::Identifier(name),
+            ..
+        }) => name,
+        t => return Err(expected("an identifier", t)),
+    };
+    match tokens.next() {
+        Some(&SpannedToken {
+            token: Token::Bracket(Direction::Left),
+            ..
+        }) => {}
+        t => return Err(expected("'('", t)),
+    }
+    let mut parameters = Vec::new();
+    loop {
+        let name = match tokens.next() {
+            Some(&SpannedToken {
+                token: Token::Bracket(Direction::Right),
+                ..
+            }) if parameters.is_empty() => break,
+            Some(&SpannedToken {
+                token: Token::Identifier(name),
+                ..
+            }) => name,
+            t => return Err(expected("a parameter", t)),
+        };
+        match tokens.next() {
+            Some(&SpannedToken {
+                token: Token::Colon,
+                ..
+            }) => {}
+            t => return Err(expected("':'", t)),
+        }
+        parameters.push(Parameter {
+            name,
+            typ: parse_type(&mut tokens, end, end_span)?,
+        });
+        match tokens.next() {
+            Some(&SpannedToken {
+                token: Token::Comma,
+                ..
+            }) => {}
+            Some(&SpannedToken {
+                token: Token::Bracket(Direction::Right),
+                ..
+            }) => break,
+            t => return Err(expected("',' or ')'", t)),
+        }
+    }
+    let return_type = if function {
+        match tokens.next() {
+            Some(&SpannedToken {
+                token: Token::Colon,
+                ..
+            }) => {}
+            t => return Err(expected("':'", t)),
         }
+        Some(parse_type(&mut tokens, end, end_span)?)
+    } else {
+        None
+    };
+    match tokens.next() {
+        Some(t) => Err(expected("'do'", Some(t))),
+        None => Ok((name, parameters, return_type)),
     }
 }
 
-fn parse_expression(tokens: &[Token]) -> Expression {
-    match tokens.len() {
-        0 => panic!("tried to parse an expression but there was nothing to parse."),
-        // There's only one token to handle so it must be a singleton expression.
-        1 => {
-            let operand = match_operand(tokens[0].clone());
-            Expression::Singleton(operand)
+/// A precedence climbing parser that goes through the tokens of an expression once.
+struct ExpressionParser<'t> {
+    tokens: &'t [SpannedToken],
+    position: usize,
+    end: &'t Token,
+    end_span: Span,
+    next_id: &'t mut u32,
+    /// How many operands the one being parsed is nested in.
+    depth: usize,
+}
+
+impl<'t> ExpressionParser<'t> {
+    fn next(&mut self) -> Option<&'t SpannedToken> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        token
+    }
+
+    /// Whether the next token is ´token´.
+    fn at(&self, token: &Token) -> bool {
+        self.tokens.get(self.position).is_some_and(|t| t.token == *token)
+    }
+
+    fn node_id(&mut self) -> NodeId {
+        let id = NodeId(*self.next_id);
+        *self.next_id += 1;
+        id
+    }
+
+    fn expression_node(&mut self, kind: ExpressionKind, span: Span) -> Expression {
+        Expression {
+            id: self.node_id(),
+            span,
+            kind,
+        }
+    }
+
+    /// Expressions that are used as operands are wrapped into ´OperandKind::Expr´ unless they
+    /// are a single operand already. The operand keeps the span of the expression, which
+    /// includes the brackets around it.
+    fn make_operand(&mut self, expression: Expression) -> Operand {
+        match expression.kind {
+            ExpressionKind::Singleton(operand) => Operand {
+                span: expression.span,
+                ..operand
+            },
+            kind => Operand {
+                id: self.node_id(),
+                span: expression.span,
+                kind: OperandKind::Expr(Box::new(Expression { kind, ..expression })),
+            },
         }
+    }
+
+    /// An error for finding ´found´ or the end of the expression when ´expected´ was expected.
+    fn expected(&self, expected: &'static str, found: Option<&SpannedToken>) -> ParseError {
+        expected_error(expected, found, self.end, self.end_span)
+    }
 
-        // a Two token expression must be an unary operator and an operand that isn't an expression.
-        2 => {
-            let operator = match_unary_operator(tokens[0].clone());
-            let operand = match_operand(tokens[1].clone());
-            Expression::Unary(operator, operand)
+    /// Parses operands joined by binary operators that bind at least as tightly as ´min_precedence´.
+    fn expression(&mut self, min_precedence: u8) -> Result<Expression, ParseError> {
+        let mut left = self.unary()?;
+        while let Some(operator) = self.tokens
+            .get(self.position)
+            .and_then(|t| match_binary_operator(&t.token))
+        {
+            let precedence = operator.precedence();
+            if precedence < min_precedence {
+                break;
+            }
+            self.position += 1;
+            // Only operators that bind more tightly are taken into the right operand which makes
+            // operators of the same precedence left associative.
+            let right = self.expression(precedence + 1)?;
+            let span = left.span.to(right.span);
+            let left_operand = self.make_operand(left);
+            let right_operand = self.make_operand(right);
+            left = self.expression_node(
+                ExpressionKind::Binary(left_operand, operator, right_operand),
+                span,
+            );
         }
-        // a Three token expression must be a binary expression with both of the Operands
-        // being non-expression.
-        3 => {
-            let operand1 = match_operand(tokens[0].clone());
-            let operator = match_binary_operator(tokens[1].clone());
-            let operand2 = match_operand(tokens[2].clone());
-            Expression::Binary(operand1, operator, operand2)
+        Ok(left)
+    }
+
+    // <unary> ::= "!" <unary> | <opnd>
+    fn unary(&mut self) -> Result<Expression, ParseError> {
+        let token = match self.next() {
+            Some(token) => token,
+            None => return Err(self.expected("an operand", None)),
+        };
+        // Every level of nesting goes deeper into the stack, so the levels are limited to keep
+        // expressions like ´((((...))))´ from overflowing it.
+        if self.depth == MAX_NESTING {
+            return Err(ParseError {
+                kind: ParseErrorKind::NestedTooDeeply,
+                span: token.span,
+            });
         }
-        _ => {
-            match tokens[0] {
-                Token::Bracket(Direction::Left) => {
-                    let closing_index = find_closing_bracket_index(tokens);
-                    let operand1 =
-                        Operand::Expr(Box::new(parse_expression(&tokens[1..closing_index])));
-                    // the operand is the whole expression therefore we return an singleton expression.
-                    match tokens.len() - (closing_index + 1) {
-                        0 => Expression::Singleton(operand1),
-                        1 => panic!("expected an operator and operand after a expression in parenthesis. found only {:#?}", tokens[closing_index+1]),
-                        2 => {
-                            let operator = match_binary_operator(tokens[closing_index + 1].clone());
-                            let operand2 = match_operand(tokens[closing_index + 2].clone());
-                            Expression::Binary(operand1, operator, operand2)
-                        },
-                        _ => {
-                            let operator = match_binary_operator(tokens[closing_index + 1].clone());
-                            let opening_index = closing_index + 2;
-                            let closing_index_2 = opening_index + find_closing_bracket_index(&tokens[opening_index..]);
-                            let operand2 = Operand::Expr(Box::new(parse_expression(&tokens[opening_index..(closing_index_2+1)])));
-                            Expression::Binary(operand1, operator, operand2)
-                        }
+        self.depth += 1;
+        let operand = self.operand(token);
+        self.depth -= 1;
+        operand
+    }
+
+    /// Parses the operand that starts with ´token´.
+    fn operand(&mut self, token: &'t SpannedToken) -> Result<Expression, ParseError> {
+        match token.token {
+            Token::Operator(Operator::Not) => {
+                let operand = self.unary()?;
+                let span = token.span.to(operand.span);
+                let operand = self.make_operand(operand);
+                Ok(self.expression_node(ExpressionKind::Unary(UnaryOperator::Not, operand), span))
+            }
+            Token::Bracket(Direction::Left) => {
+                let mut expression = self.expression(0)?;
+                match self.next() {
+                    Some(&SpannedToken {
+                        token: Token::Bracket(Direction::Right),
+                        span,
+                        ..
+                    }) => {
+                        // The brackets are a part of the expression they surround.
+                        expression.span = token.span.to(span);
+                        Ok(expression)
                     }
+                    t => Err(self.expected("')'", t)),
                 }
-                Token::Operator(Operator::Not) => {
-                    let closing_index = 1 + find_closing_bracket_index(&tokens[1..]);
-                    if closing_index + 1 == tokens.len() {
-                        let operand =
-                            Operand::Expr(Box::new(parse_expression(&tokens[2..tokens.len() - 1])));
-                        Expression::Unary(UnaryOperator::Not, operand)
-                    } else {
-                        panic!("invalid operand");
+            }
+            Token::Identifier(name) if self.at(&Token::SquareBracket(Direction::Left)) => {
+                self.position += 1;
+                let index = self.expression(0)?;
+                match self.next() {
+                    Some(&SpannedToken {
+                        token: Token::SquareBracket(Direction::Right),
+                        span,
+                        ..
+                    }) => {
+                        let span = token.span.to(span);
+                        let operand = Operand {
+                            id: self.node_id(),
+                            span,
+                            kind: OperandKind::Index(name, Box::new(index)),
+                        };
+                        Ok(self.expression_node(ExpressionKind::Singleton(operand), span))
                     }
+                    t => Err(self.expected("']'", t)),
                 }
-                Token::Identifier(_) | Token::Number(_) | Token::StringLiteral(_) => {
-                    let operand1 = match_operand(tokens[0].clone());
-                    let operator = match_binary_operator(tokens[1].clone());
-
-                    let closing_index = 3 + find_closing_bracket_index(&tokens[3..]);
-                    if closing_index + 1 == tokens.len() {
-                        let operand2 =
-                            Operand::Expr(Box::new(parse_expression(&tokens[2..tokens.len() - 1])));
-                        Expression::Binary(operand1, operator, operand2)
-                    } else {
-                        panic!("invalid operand");
-                    }
+            }
+            Token::Identifier(name) if self.at(&Token::Bracket(Direction::Left)) => {
+                let (arguments, end) = self.arguments()?;
+                let span = token.span.to(end);
+                let operand = Operand {
+                    id: self.node_id(),
+                    span,
+                    kind: OperandKind::Call(name, arguments),
+                };
+                Ok(self.expression_node(ExpressionKind::Singleton(operand), span))
+            }
+            ref t => match match_operand(t) {
+                Some(kind) => {
+                    let operand = Operand {
+                        id: self.node_id(),
+                        span: token.span,
+                        kind,
+                    };
+                    Ok(self.expression_node(ExpressionKind::Singleton(operand), token.span))
                 }
-                _ => panic!("expression started with and invalid token {:#?}"),
+                None => Err(self.expected("an operand", Some(token))),
+            },
+        }
+    }
+
+    // "(" [ <expr> ( "," <expr> )* ] ")"
+    /// Parses the arguments of a call. Returns them with the location of the closing bracket.
+    fn arguments(&mut self) -> Result<(Vec<Expression>, Span), ParseError> {
+        match self.next() {
+            Some(&SpannedToken {
+                token: Token::Bracket(Direction::Left),
+                ..
+            }) => {}
+            t => return Err(self.expected("'('", t)),
+        }
+        let mut arguments = Vec::new();
+        if let Some(&SpannedToken {
+            token: Token::Bracket(Direction::Right),
+            span,
+            ..
+        }) = self.tokens.get(self.position)
+        {
+            self.position += 1;
+            return Ok((arguments, span));
+        }
+        loop {
+            arguments.push(self.expression(0)?);
+            match self.next() {
+                Some(&SpannedToken {
+                    token: Token::Comma,
+                    ..
+                }) => {}
+                Some(&SpannedToken {
+                    token: Token::Bracket(Direction::Right),
+                    span,
+                    ..
+                }) => return Ok((arguments, span)),
+                t => return Err(self.expected("',' or ')'", t)),
             }
         }
     }
 }
 
-/// a matches for the single token operands and then
-fn match_operand(token: Token) -> Operand {
-    match token {
-        Token::Identifier(i) => Operand::Identifier(i),
-        Token::Number(n) => Operand::Int(n),
-        Token::StringLiteral(s) => Operand::StringLiteral(s),
-        _ => panic!("expected an operand but found a {:#?} instead"),
+/// Returns the type the token stands for or None if it isn't one.
+fn match_type(token: &Token) -> Option<Type> {
+    match *token {
+        Token::KeyWord(KeyWord::Int) => Some(Type::Int),
+        Token::KeyWord(KeyWord::String) => Some(Type::Str),
+        Token::KeyWord(KeyWord::Bool) => Some(Type::Bool),
+        _ => None,
     }
 }
 
-fn match_unary_operator(token: Token) -> UnaryOperator {
-    match token {
-        Token::Operator(Operator::Not) => UnaryOperator::Not,
-        t => panic!("expected an unary operator, found {:#?} instead", t),
+/// Returns the operand the token stands for or None if it isn't one.
+fn match_operand(token: &Token) -> Option<OperandKind> {
+    match *token {
+        Token::Identifier(i) => Some(OperandKind::Identifier(i)),
+        Token::Number(ref n) => Some(OperandKind::Int(n.clone())),
+        Token::StringLiteral(s) => Some(OperandKind::StringLiteral(s)),
+        Token::KeyWord(KeyWord::True) => Some(OperandKind::Bool(true)),
+        Token::KeyWord(KeyWord::False) => Some(OperandKind::Bool(false)),
+        _ => None,
     }
 }
 
-fn match_binary_operator(token: Token) -> BinaryOperator {
-    match token {
-        Token::Operator(o) => match o {
+/// Returns the binary operator the token stands for or None if it isn't one.
+fn match_binary_operator(token: &Token) -> Option<BinaryOperator> {
+    match *token {
+        Token::Operator(ref o) => Some(match *o {
             Operator::And => BinaryOperator::And,
             Operator::Divide => BinaryOperator::Divide,
             Operator::Equals => BinaryOperator::Equals,
             Operator::LessThan => BinaryOperator::LessThan,
+            Operator::GreaterThan => BinaryOperator::GreaterThan,
+            Operator::LessThanOrEqual => BinaryOperator::LessThanOrEqual,
+            Operator::GreaterThanOrEqual => BinaryOperator::GreaterThanOrEqual,
+            Operator::NotEquals => BinaryOperator::NotEquals,
+            Operator::Or => BinaryOperator::Or,
             Operator::Minus => BinaryOperator::Minus,
             Operator::Multiply => BinaryOperator::Multiply,
             Operator::Plus => BinaryOperator::Plus,
-            _ => panic!("expected a binary operator but found a {:#?} instead", o),
-        },
-        _ => panic!("expected an operator but found {:#?} instead"),
-    }
-}
-
-fn find_closing_bracket_index(tokens: &[Token]) -> usize {
-    let mut opened_brackets = 0;
-    for (i, t) in tokens.iter().enumerate() {
-        match i {
-            0 => match t {
-                &Token::Bracket(Direction::Left) => opened_brackets += 1,
-                _ => panic!("the first token was a {:#?} instead of an opening bracket"),
-            },
-            _ => match t {
-                &Token::Bracket(Direction::Left) => opened_brackets += 1,
-                &Token::Bracket(Direction::Right) => {
-                    opened_brackets -= 1;
-                    if opened_brackets == 0 {
-                        return i;
-                    }
-                }
-                _ => {}
-            },
-        }
+            Operator::Not => return None,
+        }),
+        _ => None,
     }
-    panic!("could not find a closing bracket for expression.");
 }
 
 struct State<'a, O>(fn(&mut Parser<'a, O>, Token) -> State<'a, O>)
@@ -0,0 +1,610 @@
+// Syntax trees as S-expressions. Every statement is a list that starts with the keyword or symbol
+// of the statement:
+//
+//   (var <name> <type> [<expression>] [(doc <string> ...)])
+//   (:= <name> <expression>)
+//   (:= (index <name> <expression>) <expression>)
+//   (for <name> <expression> <expression> <statement> ...)
+//   (if <expression> (then <statement> ...) [(else <statement> ...)])
+//   (while <expression> <statement> ...)
+//   (procedure <name> ((<name> <type>) ...) <statement> ...)
+//   (function <name> ((<name> <type>) ...) <type> <statement> ...)
+//   (call <name> <expression> ...)
+//   (return [<expression>])
+//   (read <name>)
+//   (print <expression>)
+//   (assert <expression>)
+//
+// Binary and unary expressions are lists that start with the operator, e.g. (+ a (* b 2)) and
+// (! done). Elements of arrays are written as (index <name> <expression>) and function calls in
+// expressions like call statements. Types are written like in Mini-PL except for array types,
+// which are written as (array <size> <type>). Numbers, strings, booleans and identifiers are
+// written like in Mini-PL. S-expressions are read with the Mini-PL scanner so the spans of the
+// nodes read from them point into the S-expressions. The scanner knows the keywords of the
+// extended dialect, so names that are keywords there, which standard Mini-PL programs can use,
+// are written as (identifier "<name>").
+
+use std::collections::VecDeque;
+use std::sync::OnceLock;
+
+use num::ToPrimitive;
+
+use scanner::{Dialect, KeyWord, LexerConfig, Operator, Scanner, SpannedToken, Token};
+use symbol::Symbol;
+use util::{Direction, Span};
+
+use super::{check_structure, match_binary_operator, match_operand, match_type, Expression, ExpressionKind, LoadError,
+            NodeId, Operand, OperandKind, Parameter, ParseErrorKind, Statement, StatementKind, Type, UnaryOperator,
+            MAX_ARRAY_SIZE};
+
+/// The text of one indentation level.
+const INDENT: &str = "  ";
+
+pub fn to_sexp(statements: &[Statement]) -> String {
+    let mut output = String::new();
+    for statement in statements {
+        write_statement(&mut output, statement, 0);
+        output.push('\n');
+    }
+    output
+}
+
+fn write_statement(output: &mut String, statement: &Statement, indent: usize) {
+    match statement.kind {
+        StatementKind::Declaration(name, ref typ, ref value, ref docs) => {
+            output.push_str(&format!("(var {} {}", write_name(name), type_to_sexp(typ)));
+            if let Some(ref value) = *value {
+                output.push(' ');
+                write_expression(output, value);
+            }
+            if !docs.is_empty() {
+                output.push_str(" (doc");
+                for doc in docs {
+                    output.push_str(&format!(" {}", Token::StringLiteral(*doc)));
+                }
+                output.push(')');
+            }
+        }
+        StatementKind::Assignment(name, ref value) => {
+            output.push_str(&format!("(:= {} ", write_name(name)));
+            write_expression(output, value);
+        }
+        StatementKind::IndexedAssignment(name, ref index, ref value) => {
+            output.push_str(&format!("(:= (index {} ", write_name(name)));
+            write_expression(output, index);
+            output.push_str(") ");
+            write_expression(output, value);
+        }
+        StatementKind::For(variable, ref from, ref to, ref body) => {
+            output.push_str(&format!("(for {} ", write_name(variable)));
+            write_expression(output, from);
+            output.push(' ');
+            write_expression(output, to);
+            write_body(output, body, indent + 1);
+        }
+        StatementKind::If(ref condition, ref then, ref otherwise) => {
+            output.push_str("(if ");
+            write_expression(output, condition);
+            write_line(output, indent + 1);
+            output.push_str("(then");
+            write_body(output, then, indent + 2);
+            output.push(')');
+            if !otherwise.is_empty() {
+                write_line(output, indent + 1);
+                output.push_str("(else");
+                write_body(output, otherwise, indent + 2);
+                output.push(')');
+            }
+        }
+        StatementKind::While(ref condition, ref body) => {
+            output.push_str("(while ");
+            write_expression(output, condition);
+            write_body(output, body, indent + 1);
+        }
+        StatementKind::Procedure(name, ref parameters, ref return_type, ref body) => {
+            let keyword = if return_type.is_some() { "function" } else { "procedure" };
+            output.push_str(&format!("({} {} (", keyword, write_name(name)));
+            for (i, parameter) in parameters.iter().enumerate() {
+                if i > 0 {
+                    output.push(' ');
+                }
+                let typ = type_to_sexp(&parameter.typ);
+                output.push_str(&format!("({} {})", write_name(parameter.name), typ));
+            }
+            output.push(')');
+            if let Some(ref return_type) = *return_type {
+                output.push_str(&format!(" {}", type_to_sexp(return_type)));
+            }
+            write_body(output, body, indent + 1);
+        }
+        StatementKind::Call(name, ref arguments) => write_call(output, name, arguments),
+        StatementKind::Return(ref value) => {
+            output.push_str("(return");
+            if let Some(ref value) = *value {
+                output.push(' ');
+                write_expression(output, value);
+            }
+        }
+        StatementKind::Read(name) => output.push_str(&format!("(read {}", write_name(name))),
+        StatementKind::Print(ref value) => {
+            output.push_str("(print ");
+            write_expression(output, value);
+        }
+        StatementKind::Assert(ref value) => {
+            output.push_str("(assert ");
+            write_expression(output, value);
+        }
+    }
+    output.push(')');
+}
+
+/// Writes each statement on a line of its own.
+fn write_body(output: &mut String, statements: &[Statement], indent: usize) {
+    for statement in statements {
+        write_line(output, indent);
+        write_statement(output, statement, indent);
+    }
+}
+
+fn write_line(output: &mut String, indent: usize) {
+    output.push('\n');
+    for _ in 0..indent {
+        output.push_str(INDENT);
+    }
+}
+
+fn write_expression(output: &mut String, expression: &Expression) {
+    match expression.kind {
+        ExpressionKind::Binary(ref left, ref operator, ref right) => {
+            output.push_str(&format!("({} ", operator));
+            write_operand(output, left);
+            output.push(' ');
+            write_operand(output, right);
+            output.push(')');
+        }
+        ExpressionKind::Unary(ref operator, ref operand) => {
+            output.push_str(&format!("({} ", operator));
+            write_operand(output, operand);
+            output.push(')');
+        }
+        ExpressionKind::Singleton(ref operand) => write_operand(output, operand),
+    }
+}
+
+fn write_operand(output: &mut String, operand: &Operand) {
+    match operand.kind {
+        OperandKind::Int(ref n) => output.push_str(&n.to_string()),
+        OperandKind::StringLiteral(s) => output.push_str(&Token::StringLiteral(s).to_string()),
+        OperandKind::Bool(b) => output.push_str(&b.to_string()),
+        OperandKind::Identifier(name) => output.push_str(&write_name(name)),
+        OperandKind::Index(name, ref index) => {
+            output.push_str(&format!("(index {} ", write_name(name)));
+            write_expression(output, index);
+            output.push(')');
+        }
+        OperandKind::Call(name, ref arguments) => {
+            write_call(output, name, arguments);
+            output.push(')');
+        }
+        OperandKind::Expr(ref expression) => write_expression(output, expression),
+    }
+}
+
+fn type_to_sexp(typ: &Type) -> String {
+    match *typ {
+        Type::Array(size, ref element) => format!("(array {} {})", size, type_to_sexp(element)),
+        ref typ => typ.to_string(),
+    }
+}
+
+/// The name as it is written in S-expressions, quoted if it would be read as a keyword.
+fn write_name(name: Symbol) -> String {
+    static EXTENDED: OnceLock<LexerConfig> = OnceLock::new();
+    let config = EXTENDED.get_or_init(|| LexerConfig::new(Dialect::Extended));
+    match config.keyword(&name.as_str()) {
+        Some(_) => format!("(identifier {})", Token::StringLiteral(name)),
+        None => name.to_string(),
+    }
+}
+
+/// Writes a call without the closing bracket.
+fn write_call(output: &mut String, name: Symbol, arguments: &[Expression]) {
+    output.push_str(&format!("(call {}", write_name(name)));
+    for argument in arguments {
+        output.push(' ');
+        write_expression(output, argument);
+    }
+}
+
+/// An S-expression: a token or a list of S-expressions in brackets.
+enum SExp {
+    Atom(SpannedToken),
+    List(Span, Vec<SExp>),
+}
+
+impl SExp {
+    fn span(&self) -> Span {
+        match *self {
+            SExp::Atom(ref token) => token.span,
+            SExp::List(span, _) => span,
+        }
+    }
+}
+
+pub fn from_sexp(text: &str) -> Result<Vec<Statement>, LoadError> {
+    // All keywords are known to the extended dialect.
+    let mut scanner = Scanner::with_config(LexerConfig::new(Dialect::Extended));
+    let mut tokens = VecDeque::new();
+    scanner.scan(text, &mut tokens);
+    scanner.finish(&mut tokens);
+    if let Some(error) = scanner.errors().first() {
+        return Err(LoadError::new(error.to_string()));
+    }
+    let mut statements = Vec::new();
+    while let Some(token) = tokens.pop_front() {
+        if token.token == Token::EndOfInput {
+            break;
+        }
+        statements.push(statement(read(token, &mut tokens)?)?);
+    }
+    check_structure(&statements, true, false)?;
+    Ok(statements)
+}
+
+/// Reads the S-expression that starts with ´first´.
+fn read(first: SpannedToken, tokens: &mut VecDeque<SpannedToken>) -> Result<SExp, LoadError> {
+    match first.token {
+        Token::Bracket(Direction::Left) => {
+            let mut items = Vec::new();
+            loop {
+                match tokens.pop_front() {
+                    Some(SpannedToken {
+                        token: Token::Bracket(Direction::Right),
+                        span,
+                        ..
+                    }) => return Ok(SExp::List(first.span.to(span), items)),
+                    Some(SpannedToken {
+                        token: Token::EndOfInput,
+                        ..
+                    })
+                    | None => return Err(error(first.span, "the list is missing its closing ')'")),
+                    Some(token) => items.push(read(token, tokens)?),
+                }
+            }
+        }
+        Token::Bracket(Direction::Right) => Err(error(first.span, "found a ')' without a list to close")),
+        _ => Ok(SExp::Atom(first)),
+    }
+}
+
+fn statement(sexp: SExp) -> Result<Statement, LoadError> {
+    let span = sexp.span();
+    let mut items = list(sexp, "a statement")?.into_iter();
+    let head = match items.next() {
+        Some(SExp::Atom(head)) => head.token,
+        _ => return Err(error(span, "expected a statement to start with a keyword")),
+    };
+    let kind = match head {
+        Token::KeyWord(KeyWord::Var) => {
+            let name = identifier(items.next(), span)?;
+            let typ = typ(items.next(), span)?;
+            let mut value = None;
+            let mut docs = Vec::new();
+            for item in items {
+                if let Some(doc_list) = doc_comments(&item)? {
+                    docs = doc_list;
+                } else if value.is_none() && docs.is_empty() {
+                    value = Some(expression(item)?);
+                } else {
+                    return Err(error(item.span(), "unexpected item in a declaration"));
+                }
+            }
+            StatementKind::Declaration(name, typ, value, docs)
+        }
+        Token::Assignment => match items.next() {
+            Some(SExp::List(target_span, target)) if starts_with(&target, "index") => {
+                let (name, index) = index(target.into_iter().skip(1), target_span)?;
+                StatementKind::IndexedAssignment(name, index, expression(last(items, span)?)?)
+            }
+            target => {
+                let name = identifier(target, span)?;
+                StatementKind::Assignment(name, expression(last(items, span)?)?)
+            }
+        },
+        Token::KeyWord(KeyWord::For) => {
+            let variable = identifier(items.next(), span)?;
+            let from = expression(required(items.next(), span)?)?;
+            let to = expression(required(items.next(), span)?)?;
+            let body = items.map(statement).collect::<Result<_, _>>()?;
+            StatementKind::For(variable, from, to, body)
+        }
+        Token::KeyWord(KeyWord::If) => {
+            let condition = expression(required(items.next(), span)?)?;
+            let then = branch(items.next(), KeyWord::Then, span)?;
+            let otherwise = match items.next() {
+                Some(item) => branch(Some(item), KeyWord::Else, span)?,
+                None => Vec::new(),
+            };
+            if let Some(item) = items.next() {
+                return Err(error(item.span(), "expected ')'"));
+            }
+            StatementKind::If(condition, then, otherwise)
+        }
+        Token::KeyWord(KeyWord::While) => {
+            let condition = expression(required(items.next(), span)?)?;
+            let body = items.map(statement).collect::<Result<_, _>>()?;
+            StatementKind::While(condition, body)
+        }
+        Token::KeyWord(KeyWord::Procedure) | Token::KeyWord(KeyWord::Function) => {
+            let name = identifier(items.next(), span)?;
+            let parameters = parameters(items.next(), span)?;
+            let return_type = if head == Token::KeyWord(KeyWord::Function) {
+                Some(typ(items.next(), span)?)
+            } else {
+                None
+            };
+            let body = items.map(statement).collect::<Result<_, _>>()?;
+            StatementKind::Procedure(name, parameters, return_type, body)
+        }
+        Token::Identifier(call_head) if &*call_head.as_str() == "call" => {
+            let (name, arguments) = call(items, span)?;
+            StatementKind::Call(name, arguments)
+        }
+        Token::KeyWord(KeyWord::Return) => {
+            let value = match items.next() {
+                Some(item) => Some(expression(item)?),
+                None => None,
+            };
+            if let Some(item) = items.next() {
+                return Err(error(item.span(), "expected ')'"));
+            }
+            StatementKind::Return(value)
+        }
+        Token::KeyWord(KeyWord::Read) => {
+            let name = identifier(items.next(), span)?;
+            if let Some(item) = items.next() {
+                return Err(error(item.span(), "expected ')'"));
+            }
+            StatementKind::Read(name)
+        }
+        Token::KeyWord(KeyWord::Print) => StatementKind::Print(expression(last(items, span)?)?),
+        Token::KeyWord(KeyWord::Assert) => StatementKind::Assert(expression(last(items, span)?)?),
+        head => return Err(error(span, &format!("{} doesn't start a statement", head))),
+    };
+    Ok(Statement {
+        id: NodeId::DUMMY,
+        span,
+        kind,
+    })
+}
+
+/// Reads a ´(then ...)´ or ´(else ...)´ list of an if statement.
+fn branch(sexp: Option<SExp>, keyword: KeyWord, list_span: Span) -> Result<Vec<Statement>, LoadError> {
+    let sexp = required(sexp, list_span)?;
+    let span = sexp.span();
+    let mut items = list(sexp, "a branch of an if statement")?.into_iter();
+    match items.next() {
+        Some(SExp::Atom(SpannedToken {
+            token: Token::KeyWord(ref head),
+            ..
+        })) if *head == keyword => items.map(statement).collect(),
+        _ => Err(error(span, &format!("expected the branch to start with {}", keyword))),
+    }
+}
+
+/// Reads a ´((<name> <type>) ...)´ list of parameters.
+fn parameters(sexp: Option<SExp>, list_span: Span) -> Result<Vec<Parameter>, LoadError> {
+    list(required(sexp, list_span)?, "a list of parameters")?
+        .into_iter()
+        .map(|parameter| {
+            let span = parameter.span();
+            let mut items = list(parameter, "a parameter")?.into_iter();
+            let name = identifier(items.next(), span)?;
+            let typ = typ(items.next(), span)?;
+            match items.next() {
+                Some(item) => Err(error(item.span(), "expected ')'")),
+                None => Ok(Parameter { name, typ }),
+            }
+        })
+        .collect()
+}
+
+/// Reads the name and the arguments of a call from the items after ´call´.
+fn call<I: Iterator<Item = SExp>>(mut items: I, list_span: Span) -> Result<(Symbol, Vec<Expression>), LoadError> {
+    let name = identifier(items.next(), list_span)?;
+    let arguments = items.map(expression).collect::<Result<_, _>>()?;
+    Ok((name, arguments))
+}
+
+/// Reads the name of the array and the index from the items after ´index´.
+fn index<I: Iterator<Item = SExp>>(mut items: I, list_span: Span) -> Result<(Symbol, Expression), LoadError> {
+    let name = identifier(items.next(), list_span)?;
+    let index = expression(last(items, list_span)?)?;
+    Ok((name, index))
+}
+
+/// Whether the items are those of a list that stands for an operand instead of an expression.
+fn is_operand_list(items: &[SExp]) -> bool {
+    starts_with(items, "call") || starts_with(items, "index") || starts_with(items, "identifier")
+}
+
+/// Whether the items are those of a list that starts with the identifier ´head´, e.g. a
+/// ´(call ...)´ list.
+fn starts_with(items: &[SExp], head: &str) -> bool {
+    match items.first() {
+        Some(&SExp::Atom(SpannedToken {
+            token: Token::Identifier(first),
+            ..
+        })) => &*first.as_str() == head,
+        _ => false,
+    }
+}
+
+/// Reads a ´(doc "..." ...)´ list. Returns None if the S-expression isn't one.
+fn doc_comments(sexp: &SExp) -> Result<Option<Vec<Symbol>>, LoadError> {
+    let items = match *sexp {
+        SExp::List(_, ref items) => items,
+        SExp::Atom(_) => return Ok(None),
+    };
+    match items.first() {
+        Some(&SExp::Atom(SpannedToken {
+            token: Token::Identifier(doc),
+            ..
+        })) if &*doc.as_str() == "doc" => {}
+        _ => return Ok(None),
+    }
+    items[1..]
+        .iter()
+        .map(|item| match *item {
+            SExp::Atom(SpannedToken {
+                token: Token::StringLiteral(doc),
+                ..
+            }) => Ok(doc),
+            ref item => Err(error(item.span(), "expected a doc comment string")),
+        })
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+fn expression(sexp: SExp) -> Result<Expression, LoadError> {
+    let span = sexp.span();
+    let mut items = match sexp {
+        SExp::List(_, items) if !is_operand_list(&items) => items.into_iter(),
+        sexp => {
+            let operand = operand(sexp)?;
+            return Ok(Expression {
+                id: NodeId::DUMMY,
+                span,
+                kind: ExpressionKind::Singleton(operand),
+            });
+        }
+    };
+    let operator = match items.next() {
+        Some(SExp::Atom(operator)) => operator.token,
+        _ => return Err(error(span, "expected an expression to start with an operator")),
+    };
+    let first = operand(required(items.next(), span)?)?;
+    let kind = match items.next() {
+        None if operator == Token::Operator(Operator::Not) => {
+            ExpressionKind::Unary(UnaryOperator::Not, first)
+        }
+        Some(second) => match match_binary_operator(&operator) {
+            Some(operator) => {
+                let second = operand(second)?;
+                if let Some(item) = items.next() {
+                    return Err(error(item.span(), "expected ')'"));
+                }
+                ExpressionKind::Binary(first, operator, second)
+            }
+            None => return Err(error(span, &format!("{} is not a binary operator", operator))),
+        },
+        None => return Err(error(span, &format!("{} is not a unary operator", operator))),
+    };
+    Ok(Expression {
+        id: NodeId::DUMMY,
+        span,
+        kind,
+    })
+}
+
+fn operand(sexp: SExp) -> Result<Operand, LoadError> {
+    let span = sexp.span();
+    let kind = match sexp {
+        SExp::Atom(token) => match match_operand(&token.token) {
+            Some(kind) => kind,
+            None => return Err(error(span, &format!("expected an operand but found {}", token.token))),
+        },
+        SExp::List(_, items) if starts_with(&items, "call") => {
+            let (name, arguments) = call(items.into_iter().skip(1), span)?;
+            OperandKind::Call(name, arguments)
+        }
+        SExp::List(_, items) if starts_with(&items, "index") => {
+            let (name, index) = index(items.into_iter().skip(1), span)?;
+            OperandKind::Index(name, Box::new(index))
+        }
+        SExp::List(list_span, items) if starts_with(&items, "identifier") => {
+            OperandKind::Identifier(identifier(Some(SExp::List(list_span, items)), span)?)
+        }
+        list => OperandKind::Expr(Box::new(expression(list)?)),
+    };
+    Ok(Operand {
+        id: NodeId::DUMMY,
+        span,
+        kind,
+    })
+}
+
+fn list(sexp: SExp, expected: &str) -> Result<Vec<SExp>, LoadError> {
+    match sexp {
+        SExp::List(_, items) => Ok(items),
+        SExp::Atom(token) => Err(error(token.span, &format!("expected {} but found {}", expected, token.token))),
+    }
+}
+
+fn typ(sexp: Option<SExp>, list_span: Span) -> Result<Type, LoadError> {
+    let sexp = required(sexp, list_span)?;
+    let span = sexp.span();
+    let mut items = match sexp {
+        SExp::Atom(token) => return match_type(&token.token).ok_or_else(|| error(span, "expected a type")),
+        SExp::List(_, items) => items.into_iter(),
+    };
+    // (array <size> <type>)
+    match items.next() {
+        Some(SExp::Atom(SpannedToken {
+            token: Token::KeyWord(KeyWord::Array),
+            ..
+        })) => {}
+        _ => return Err(error(span, "expected a type")),
+    }
+    let size = match items.next() {
+        Some(SExp::Atom(SpannedToken {
+            token: Token::Number(ref size),
+            ..
+        })) => size.to_usize(),
+        _ => None,
+    };
+    let size = size.ok_or_else(|| error(span, "expected an array size"))?;
+    if size > MAX_ARRAY_SIZE {
+        return Err(error(span, &ParseErrorKind::ArrayTooLarge.to_string()));
+    }
+    match typ(Some(last(items, span)?), span)? {
+        Type::Array(..) => Err(error(span, "expected the elements of an array not to be arrays")),
+        element => Ok(Type::Array(size, Box::new(element))),
+    }
+}
+
+/// Reads a name, which is an identifier or an ´(identifier "<name>")´ list.
+fn identifier(sexp: Option<SExp>, list_span: Span) -> Result<Symbol, LoadError> {
+    match required(sexp, list_span)? {
+        SExp::Atom(SpannedToken {
+            token: Token::Identifier(name),
+            ..
+        }) => Ok(name),
+        SExp::List(span, items) if starts_with(&items, "identifier") => match items.get(1..) {
+            Some(&[SExp::Atom(SpannedToken {
+                token: Token::StringLiteral(name),
+                ..
+            })]) => Ok(name),
+            _ => Err(error(span, "expected the name of the identifier as a string")),
+        },
+        sexp => Err(error(sexp.span(), "expected an identifier")),
+    }
+}
+
+/// An item that has to be in the list at ´list_span´.
+fn required(sexp: Option<SExp>, list_span: Span) -> Result<SExp, LoadError> {
+    sexp.ok_or_else(|| error(list_span, "the list is missing items"))
+}
+
+/// The last item of a list.
+fn last<I: Iterator<Item = SExp>>(mut items: I, list_span: Span) -> Result<SExp, LoadError> {
+    let item = required(items.next(), list_span)?;
+    match items.next() {
+        Some(extra) => Err(error(extra.span(), "expected ')'")),
+        None => Ok(item),
+    }
+}
+
+fn error(span: Span, message: &str) -> LoadError {
+    LoadError::new(format!("{}: {}", span, message))
+}
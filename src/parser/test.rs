@@ -1,6 +1,43 @@
 use std::collections::VecDeque;
-use super::{parse, BinaryOperator, Expression, Operand, Statement, Type};
-use scanner::Scanner;
+use super::{deserialize, from_json, from_sexp, parse, serialize, to_json, to_sexp, walk_expression,
+            walk_fold_expression, walk_fold_operand, AstFormat, BinaryOperator, Expression, ExpressionKind,
+            Folder, MAX_NESTING, NodeId, Operand, OperandKind, Parameter, ParseErrorKind, Statement, StatementKind, Type,
+            UnaryOperator, Visitor};
+use symbol::Symbol;
+use scanner::{Dialect, KeyWord, LexerConfig, Operator, Scanner, Token};
+use util::{Direction, Span};
+
+fn statement(kind: StatementKind) -> Statement {
+    kind.into()
+}
+
+fn singleton(operand: OperandKind) -> Expression {
+    ExpressionKind::Singleton(operand.into()).into()
+}
+
+fn binary(left: OperandKind, operator: BinaryOperator, right: OperandKind) -> Expression {
+    ExpressionKind::Binary(left.into(), operator, right.into()).into()
+}
+
+fn unary(operator: UnaryOperator, operand: OperandKind) -> Expression {
+    ExpressionKind::Unary(operator, operand.into()).into()
+}
+
+fn id(name: &str) -> OperandKind {
+    OperandKind::Identifier(name.into())
+}
+
+fn int(n: u32) -> OperandKind {
+    OperandKind::Int(n.into())
+}
+
+fn string(s: &str) -> OperandKind {
+    OperandKind::StringLiteral(s.into())
+}
+
+fn expr(expression: Expression) -> OperandKind {
+    OperandKind::Expr(Box::new(expression))
+}
 
 #[test]
 fn example_program_2() {
@@ -18,38 +55,918 @@ fn example_program_2() {
     scanner.scan(source, &mut tokens);
     let mut statements = VecDeque::new();
     let mut expected: VecDeque<_> = vec![
-        Statement::Declaration(
-            "nTimes".to_string(),
+        statement(StatementKind::Declaration(
+            "nTimes".into(),
             Type::Int,
-            Some(Expression::Singleton(Operand::Int(0.into()))),
-        ),
-        Statement::Print(Expression::Singleton(Operand::StringLiteral(
-            "How many times?".to_string(),
-        ))),
-        Statement::Read("nTimes".to_string()),
-        Statement::Declaration("x".to_string(), Type::Int, None),
-        Statement::For(
-            "x".to_string(),
-            Expression::Singleton(Operand::Int(0.into())),
-            Expression::Binary(
-                Operand::Identifier("nTimes".to_string()),
+            Some(singleton(int(0))),
+            vec![],
+        )),
+        statement(StatementKind::Print(singleton(string("How many times?")))),
+        statement(StatementKind::Read("nTimes".into())),
+        statement(StatementKind::Declaration("x".into(), Type::Int, None, vec![])),
+        statement(StatementKind::For(
+            "x".into(),
+            singleton(int(0)),
+            binary(
+                id("nTimes"),
                 BinaryOperator::Minus,
-                Operand::Int(1.into()),
+                int(1),
             ),
             vec![
-                Statement::Print(Expression::Singleton(Operand::Identifier("x".to_string()))),
-                Statement::Print(Expression::Singleton(Operand::StringLiteral(
-                    " : Hello, World!\n".to_string(),
-                ))),
+                statement(StatementKind::Print(singleton(id("x")))),
+                statement(StatementKind::Print(singleton(string(" : Hello, World!\n")))),
             ],
-        ),
-        Statement::Assert(Expression::Binary(
-            Operand::Identifier("x".to_string()),
-            BinaryOperator::Equals,
-            Operand::Identifier("nTimes".to_string()),
         )),
+        statement(StatementKind::Assert(binary(
+            id("x"),
+            BinaryOperator::Equals,
+            id("nTimes"),
+        ))),
     ].into_iter()
         .collect();
     parse(&mut tokens, &mut statements);
     assert_eq!(statements, expected);
 }
+
+#[test]
+fn comparison_operators() {
+    let source = "assert (x >= 1); assert (x <> y); print a | b;";
+    let mut scanner = Scanner::new();
+    let mut tokens = VecDeque::new();
+    scanner.scan(source, &mut tokens);
+    let mut statements = Vec::new();
+    parse(&mut tokens, &mut statements);
+    assert_eq!(
+        statements,
+        vec![
+            statement(StatementKind::Assert(binary(
+                id("x"),
+                BinaryOperator::GreaterThanOrEqual,
+                int(1),
+            ))),
+            statement(StatementKind::Assert(binary(
+                id("x"),
+                BinaryOperator::NotEquals,
+                id("y"),
+            ))),
+            statement(StatementKind::Print(binary(
+                id("a"),
+                BinaryOperator::Or,
+                id("b"),
+            ))),
+        ]
+    );
+}
+
+#[test]
+fn doc_comments_are_attached_to_declarations() {
+    let source = "/// Not attached.\nprint 1;\n/// How many times.\n/** Set by the user. */\nvar n : int;\n/// Not attached either.\n;\nvar s : string;";
+    let mut scanner = Scanner::new();
+    let mut tokens = VecDeque::new();
+    scanner.scan(source, &mut tokens);
+    scanner.finish(&mut tokens);
+    let mut statements = Vec::new();
+    parse(&mut tokens, &mut statements);
+    assert_eq!(
+        statements,
+        vec![
+            statement(StatementKind::Print(singleton(int(1)))),
+            statement(StatementKind::Declaration(
+                "n".into(),
+                Type::Int,
+                None,
+                vec![" How many times.".into(), " Set by the user. ".into()],
+            )),
+            statement(StatementKind::Declaration("s".into(), Type::Str, None, vec![])),
+        ]
+    );
+
+    let source = "var x /// Not the type.\n: int := 1 + /** Not a number. */ 2;\nif x = 3 then\n  /// Inside.\n  var y : int;\nend if;";
+    let (errors, statements) = parse_errors(source, Dialect::Extended);
+    assert_eq!(
+        errors,
+        vec![
+            (ParseErrorKind::MisplacedDocComment, 1),
+            (ParseErrorKind::MisplacedDocComment, 2),
+        ]
+    );
+    let docs = |statement: &Statement| match statement.kind {
+        StatementKind::Declaration(_, _, _, ref docs) => docs.clone(),
+        StatementKind::If(_, ref then, _) => match then[0].kind {
+            StatementKind::Declaration(_, _, _, ref docs) => docs.clone(),
+            ref kind => panic!("unexpected statement {:?}", kind),
+        },
+        ref kind => panic!("unexpected statement {:?}", kind),
+    };
+    assert_eq!(docs(&statements[0]), Vec::<Symbol>::new());
+    assert_eq!(docs(&statements[1]), vec![Symbol::from(" Inside.")]);
+}
+
+/// Parses a program that has no errors in the dialect.
+fn parse_source(source: &str, dialect: Dialect) -> Vec<Statement> {
+    let (errors, statements) = parse_errors(source, dialect);
+    assert_eq!(errors, vec![], "{}", source);
+    statements
+}
+
+/// Parses a program and returns the kinds and lines of its errors and the statements without any.
+fn parse_errors(source: &str, dialect: Dialect) -> (Vec<(ParseErrorKind, usize)>, Vec<Statement>) {
+    let mut scanner = Scanner::with_config(LexerConfig::new(dialect));
+    let mut tokens = VecDeque::new();
+    scanner.scan(source, &mut tokens);
+    scanner.finish(&mut tokens);
+    assert_eq!(scanner.errors(), &[][..], "{}", source);
+    let mut statements = Vec::new();
+    let errors = parse(&mut tokens, &mut statements)
+        .into_iter()
+        .map(|e| (e.kind, e.span.line))
+        .collect();
+    (errors, statements)
+}
+
+/// Checks that the statements are read back the same from JSON and S-expressions.
+fn assert_round_trips(statements: &[Statement]) {
+    assert_eq!(from_json(&to_json(statements)), Ok(statements.to_vec()));
+    assert_eq!(from_sexp(&to_sexp(statements)), Ok(statements.to_vec()));
+}
+
+fn parse_print(expression: &str) -> Expression {
+    let mut scanner = Scanner::new();
+    let mut tokens = VecDeque::new();
+    scanner.scan(&format!("print {};", expression), &mut tokens);
+    let mut statements = Vec::new();
+    parse(&mut tokens, &mut statements);
+    match statements.pop() {
+        Some(Statement {
+            kind: StatementKind::Print(expression),
+            ..
+        }) => expression,
+        s => panic!("expected a print statement, found {:?}", s),
+    }
+}
+
+#[test]
+fn operator_precedence_and_associativity() {
+    use super::BinaryOperator::*;
+
+    // a + b * c - 1 = (a + (b * c)) - 1
+    assert_eq!(
+        parse_print("a + b * c - 1"),
+        binary(
+            expr(binary(
+                id("a"),
+                Plus,
+                expr(binary(id("b"), Multiply, id("c"))),
+            )),
+            Minus,
+            int(1),
+        )
+    );
+    // !(x) & y = z = (!x) & (y = z)
+    assert_eq!(
+        parse_print("!(x) & y = z"),
+        binary(
+            expr(unary(UnaryOperator::Not, id("x"))),
+            And,
+            expr(binary(id("y"), Equals, id("z"))),
+        )
+    );
+    // x | y & !z < 2 = x | (y & ((!z) < 2))
+    assert_eq!(
+        parse_print("x | y & !z < 2"),
+        binary(
+            id("x"),
+            Or,
+            expr(binary(
+                id("y"),
+                And,
+                expr(binary(
+                    expr(unary(UnaryOperator::Not, id("z"))),
+                    LessThan,
+                    int(2),
+                )),
+            )),
+        )
+    );
+    assert_eq!(
+        parse_print("8 / 4 / 2"),
+        binary(
+            expr(binary(int(8), Divide, int(4))),
+            Divide,
+            int(2),
+        )
+    );
+    assert_eq!(
+        parse_print("(1 + 2) * ((3))"),
+        binary(
+            expr(binary(int(1), Plus, int(2))),
+            Multiply,
+            int(3),
+        )
+    );
+    assert_eq!(parse_print("((a))"), singleton(id("a")));
+
+    // Long chains are parsed in one pass without deep recursion.
+    let chain = vec!["1"; 10_000].join(" + ");
+    match parse_print(&chain) {
+        Expression {
+            kind: ExpressionKind::Binary(_, Plus, _),
+            ..
+        } => {}
+        e => panic!("unexpected expression {:?}", e),
+    }
+}
+
+#[test]
+fn expressions_can_only_be_nested_so_deep() {
+    let nested = |depth: usize, open: &str, close: &str| {
+        format!("print {}1{};", open.repeat(depth), close.repeat(depth))
+    };
+    // The innermost operand is a level of its own.
+    parse_source(&nested(MAX_NESTING - 1, "(", ")"), Dialect::Standard);
+    parse_source(&nested(MAX_NESTING - 1, "!", ""), Dialect::Standard);
+    let source = [
+        nested(MAX_NESTING, "(", ")"),
+        nested(100_000, "(", ")"),
+        nested(100_000, "!", ""),
+        nested(100_000, "(!", ")"),
+        "print 2;".to_string(),
+    ].join("\n");
+    let (errors, statements) = parse_errors(&source, Dialect::Standard);
+    assert_eq!(
+        errors,
+        vec![
+            (ParseErrorKind::NestedTooDeeply, 1),
+            (ParseErrorKind::NestedTooDeeply, 2),
+            (ParseErrorKind::NestedTooDeeply, 3),
+            (ParseErrorKind::NestedTooDeeply, 4),
+        ]
+    );
+    assert_eq!(statements.len(), 1);
+}
+
+#[test]
+fn syntax_errors_are_reported_and_parsing_continues() {
+    let source = r#"print "ok";
+var x int;
+print 1 + ;
+for i in 1 do
+  for j in 1..2 do print j; end for;
+  x := ;
+end for;
+print (2;
+end for;
+read 5;
+var y : int := 3;
+assert (y = 3;
+print y"#;
+    let mut scanner = Scanner::new();
+    let mut tokens = VecDeque::new();
+    scanner.scan(source, &mut tokens);
+    scanner.finish(&mut tokens);
+    let mut statements = Vec::new();
+    let errors = parse(&mut tokens, &mut statements);
+    assert_eq!(
+        statements,
+        vec![
+            statement(StatementKind::Print(singleton(string("ok")))),
+            statement(StatementKind::Declaration(
+                "y".into(),
+                Type::Int,
+                Some(singleton(int(3))),
+                vec![],
+            )),
+        ]
+    );
+    let errors: Vec<(ParseErrorKind, usize)> = errors.into_iter().map(|e| (e.kind, e.span.line)).collect();
+    assert_eq!(
+        errors,
+        vec![
+            (ParseErrorKind::Expected("':'", Token::KeyWord(KeyWord::Int)), 2),
+            (ParseErrorKind::Expected("an operand", Token::Semicolon), 3),
+            (ParseErrorKind::Expected("'..'", Token::KeyWord(KeyWord::Do)), 4),
+            (ParseErrorKind::Expected("an operand", Token::Semicolon), 6),
+            (ParseErrorKind::Expected("')'", Token::Semicolon), 8),
+            (ParseErrorKind::UnmatchedEnd, 9),
+            (ParseErrorKind::Expected("an identifier", Token::Number(5.into())), 10),
+            (ParseErrorKind::Expected("')'", Token::Semicolon), 12),
+            (ParseErrorKind::UnexpectedEnd("';'"), 13),
+        ]
+    );
+
+    let mut scanner = Scanner::new();
+    let mut tokens = VecDeque::new();
+    scanner.scan("for i in 1..2 do\n print i;\n", &mut tokens);
+    let errors = parse(&mut tokens, &mut Vec::new());
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, ParseErrorKind::UnclosedFor);
+    assert_eq!(errors[0].to_string(), "1:1: the for loop is missing its end for");
+}
+
+#[test]
+fn nodes_have_spans_and_unique_ids() {
+    fn operand_ids(operand: &Operand, ids: &mut Vec<NodeId>) {
+        ids.push(operand.id);
+        if let OperandKind::Expr(ref e) = operand.kind {
+            expression_ids(e, ids);
+        }
+    }
+    fn expression_ids(expression: &Expression, ids: &mut Vec<NodeId>) {
+        ids.push(expression.id);
+        match expression.kind {
+            ExpressionKind::Binary(ref l, _, ref r) => {
+                operand_ids(l, ids);
+                operand_ids(r, ids);
+            }
+            ExpressionKind::Unary(_, ref o) | ExpressionKind::Singleton(ref o) => operand_ids(o, ids),
+        }
+    }
+
+    let source = "var x : int := (a + 1) * b;\n  print x;\nprint (x) + !((y));";
+    let statements = parse_source(source, Dialect::Standard);
+    assert_eq!(statements.len(), 3);
+    assert_eq!(
+        statements[0].span,
+        Span {
+            start: 0,
+            end: 26,
+            line: 1,
+            column: 1,
+        }
+    );
+    assert_eq!(&source[statements[1].span.start..statements[1].span.end], "print x");
+    assert_eq!((statements[1].span.line, statements[1].span.column), (2, 3));
+
+    let initializer = match statements[0].kind {
+        StatementKind::Declaration(_, _, Some(ref e), _) => e,
+        ref s => panic!("expected a declaration with a value, found {:?}", s),
+    };
+    assert_eq!(&source[initializer.span.start..initializer.span.end], "(a + 1) * b");
+    match initializer.kind {
+        ExpressionKind::Binary(ref left, _, ref right) => {
+            // The brackets belong to the parenthesised operand.
+            assert_eq!(&source[left.span.start..left.span.end], "(a + 1)");
+            assert_eq!((right.span.start, right.span.column), (25, 26));
+        }
+        ref e => panic!("expected a binary expression, found {:?}", e),
+    }
+    match statements[2].kind {
+        StatementKind::Print(Expression {
+            kind: ExpressionKind::Binary(ref left, _, ref right),
+            ..
+        }) => {
+            assert_eq!(&source[left.span.start..left.span.end], "(x)");
+            match right.kind {
+                OperandKind::Expr(ref e) => match e.kind {
+                    ExpressionKind::Unary(_, ref y) => assert_eq!(&source[y.span.start..y.span.end], "((y))"),
+                    ref e => panic!("expected a unary expression, found {:?}", e),
+                },
+                ref o => panic!("expected an expression operand, found {:?}", o),
+            }
+        }
+        ref s => panic!("expected a print of a binary expression, found {:?}", s),
+    }
+
+    let mut ids: Vec<NodeId> = statements.iter().map(|s| s.id).collect();
+    expression_ids(initializer, &mut ids);
+    if let StatementKind::Print(ref e) = statements[1].kind {
+        expression_ids(e, &mut ids);
+    }
+    assert!(ids.iter().all(|&id| id != NodeId::DUMMY));
+    let mut unique = ids.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(unique.len(), ids.len());
+
+    // Parsing the same source again gives the same ids.
+    let again = parse_source(source, Dialect::Standard);
+    assert_eq!(again[0].id, statements[0].id);
+    assert_eq!(again[1].id, statements[1].id);
+}
+
+#[test]
+fn visitors_only_handle_the_nodes_they_override() {
+    // Collects the variables that are read in expressions.
+    struct Uses(Vec<Symbol>);
+    impl Visitor for Uses {
+        fn visit_operand(&mut self, operand: &Operand) {
+            if let OperandKind::Identifier(i) = operand.kind {
+                self.0.push(i);
+            }
+            super::walk_operand(self, operand);
+        }
+    }
+    // Counts the binary expressions but doesn't look inside them.
+    struct Shallow(usize);
+    impl Visitor for Shallow {
+        fn visit_expression(&mut self, expression: &Expression) {
+            match expression.kind {
+                ExpressionKind::Binary(..) => self.0 += 1,
+                _ => walk_expression(self, expression),
+            }
+        }
+    }
+
+    let source = "var x : int := a; for i in 0..(n - 1) do print !(x * (i + b)); end for; read y;";
+    let statements = parse_source(source, Dialect::Standard);
+    let mut uses = Uses(Vec::new());
+    let mut shallow = Shallow(0);
+    for statement in &statements {
+        uses.visit_statement(statement);
+        shallow.visit_statement(statement);
+    }
+    let expected: Vec<Symbol> = vec!["a".into(), "n".into(), "x".into(), "i".into(), "b".into()];
+    assert_eq!(uses.0, expected);
+    assert_eq!(shallow.0, 2);
+}
+
+#[test]
+fn folders_rewrite_the_tree() {
+    // Adds up sums of two number literals.
+    struct ConstantFolder;
+    impl Folder for ConstantFolder {
+        fn fold_expression(&mut self, expression: Expression) -> Expression {
+            let expression = walk_fold_expression(self, expression);
+            let sum = match expression.kind {
+                ExpressionKind::Binary(
+                    Operand {
+                        kind: OperandKind::Int(ref left),
+                        ..
+                    },
+                    BinaryOperator::Plus,
+                    Operand {
+                        kind: OperandKind::Int(ref right),
+                        ..
+                    },
+                ) => left + right,
+                _ => return expression,
+            };
+            let operand = Operand {
+                id: NodeId::DUMMY,
+                span: expression.span,
+                kind: OperandKind::Int(sum),
+            };
+            Expression {
+                kind: ExpressionKind::Singleton(operand),
+                ..expression
+            }
+        }
+
+        fn fold_operand(&mut self, operand: Operand) -> Operand {
+            // Brackets around a single operand are no longer needed.
+            match walk_fold_operand(self, operand) {
+                Operand {
+                    kind: OperandKind::Expr(expression),
+                    ..
+                } => match *expression {
+                    Expression {
+                        kind: ExpressionKind::Singleton(operand),
+                        ..
+                    } => operand,
+                    expression => OperandKind::Expr(Box::new(expression)).into(),
+                },
+                operand => operand,
+            }
+        }
+    }
+
+    let statements = parse_source("for i in 1 + 2..x do print (1 + 2) + 3 * (4 + 5); end for;", Dialect::Standard);
+    let folded: Vec<Statement> = statements
+        .iter()
+        .cloned()
+        .map(|s| ConstantFolder.fold_statement(s))
+        .collect();
+    assert_eq!(
+        folded,
+        vec![
+            statement(StatementKind::For(
+                "i".into(),
+                singleton(int(3)),
+                singleton(id("x")),
+                vec![
+                    statement(StatementKind::Print(binary(
+                        int(3),
+                        BinaryOperator::Plus,
+                        expr(binary(int(3), BinaryOperator::Multiply, int(9))),
+                    ))),
+                ],
+            )),
+        ]
+    );
+    // Nodes that were only rebuilt keep their IDs and spans.
+    assert_eq!(folded[0].id, statements[0].id);
+    assert_eq!(folded[0].span, statements[0].span);
+}
+
+#[test]
+fn syntax_trees_are_written_and_read_as_json() {
+    let source = "/// Counter.\nvar x : int := 1;\nfor i in 1..3 do\n  x := x * (i + 1);\nend for;\nread s;\nprint \"x=\" ;\nassert (!(x = 1));";
+    let statements = parse_source(source, Dialect::Standard);
+    let json = to_json(&statements);
+    assert_eq!(json[0]["type"], "declaration");
+    assert_eq!(json[0]["var_type"], "int");
+    assert_eq!(json[0]["docs"][0], " Counter.");
+    assert_eq!(json[0]["value"], json!({"type": "int", "value": 1, "span": json[0]["value"]["span"]}));
+    assert_eq!(json[0]["span"], json!({"start": 13, "end": 29, "line": 2, "column": 1}));
+    let product = &json[1]["body"][0]["value"];
+    assert_eq!(product["operator"], "*");
+    assert_eq!(product["right"]["type"], "binary");
+    assert_eq!(product["right"]["left"]["name"], "i");
+    assert_eq!(json[4]["value"]["type"], "unary");
+    assert_eq!(from_json(&json), Ok(statements.clone()));
+    let text = serialize(&statements, AstFormat::Json);
+    let loaded = deserialize(&text, AstFormat::Json).unwrap();
+    assert_eq!(loaded, statements);
+    assert_eq!(loaded[1].span, statements[1].span);
+
+    // Trees made by other tools don't need spans, docs or small numbers.
+    let generated = json!([
+        {"type": "declaration", "name": "n", "var_type": "int"},
+        {"type": "assignment", "name": "n", "value": {
+            "type": "binary", "operator": "+",
+            "left": {"type": "identifier", "name": "n"},
+            "right": {"type": "int", "value": "100000000000000000000"}
+        }},
+        {"type": "print", "value": {"type": "string", "value": "done"}}
+    ]);
+    assert_eq!(
+        from_json(&generated),
+        Ok(vec![
+            statement(StatementKind::Declaration("n".into(), Type::Int, None, vec![])),
+            statement(StatementKind::Assignment(
+                "n".into(),
+                binary(
+                    id("n"),
+                    BinaryOperator::Plus,
+                    OperandKind::Int("100000000000000000000".parse().unwrap()),
+                ),
+            )),
+            statement(StatementKind::Print(singleton(string("done")))),
+        ])
+    );
+    let error = from_json(&json!([{"type": "print"}])).unwrap_err();
+    assert!(error.to_string().contains("\"value\""), "{}", error);
+    assert!(from_json(&json!([{"type": "loop"}])).is_err());
+    assert!(deserialize("[{", AstFormat::Json).is_err());
+    let big = "[{\"type\": \"print\", \"value\": {\"type\": \"binary\", \"operator\": \"-\",
+        \"left\": {\"type\": \"int\", \"value\": 9223372036854775808},
+        \"right\": {\"type\": \"int\", \"value\": 100000000000000000000}}}]";
+    assert_eq!(
+        deserialize(big, AstFormat::Json),
+        Ok(vec![statement(StatementKind::Print(binary(
+            OperandKind::Int("9223372036854775808".parse().unwrap()),
+            BinaryOperator::Minus,
+            OperandKind::Int("100000000000000000000".parse().unwrap()),
+        )))])
+    );
+    assert!(from_json(&json!([{"type": "print", "value": {"type": "int", "value": 1.5}}])).is_err());
+}
+
+#[test]
+fn syntax_trees_are_written_and_read_as_s_expressions() {
+    let source = "/// Counter.\nvar x : int := 1;\nfor i in 1..3 do\n  x := x * (i + 1);\n  print \"\\\"\\n\";\nend for;\nread s;\nassert (!(x = 1) | s <> \"\");";
+    let statements = parse_source(source, Dialect::Standard);
+    let sexp = to_sexp(&statements);
+    assert_eq!(
+        sexp,
+        r#"(var x int 1 (doc " Counter."))
+(for i 1 3
+  (:= x (* x (+ i 1)))
+  (print "\"\n"))
+(read s)
+(assert (| (! (= x 1)) (!= s "")))
+"#
+    );
+    assert_eq!(from_sexp(&sexp), Ok(statements.clone()));
+    assert_eq!(deserialize(&serialize(&statements, AstFormat::SExpression), AstFormat::SExpression), Ok(statements));
+
+    assert_eq!(
+        from_sexp("(var b bool (doc \"a\" \"b\"))\n(print (- 5 2))"),
+        Ok(vec![
+            statement(StatementKind::Declaration(
+                "b".into(),
+                Type::Bool,
+                None,
+                vec!["a".into(), "b".into()],
+            )),
+            statement(StatementKind::Print(binary(int(5), BinaryOperator::Minus, int(2)))),
+        ])
+    );
+    for invalid in &["(print 1", "(print 1))", "(print)", "(print (+ 1))", "(print (! 1 2))", "(x 1)", "(read 1)"] {
+        assert!(from_sexp(invalid).is_err(), "{} was accepted", invalid);
+    }
+    let error = from_sexp("(print 1)\n(var x int (+ 1))").unwrap_err();
+    assert_eq!(error.to_string(), "invalid syntax tree: 2:12: + is not a unary operator");
+}
+
+#[test]
+fn loaded_syntax_trees_are_checked_like_parsed_ones() {
+    let error = from_sexp("(print 1)\n(while true\n  (return))").unwrap_err();
+    assert_eq!(error.to_string(), "invalid syntax tree: 3:3: found a return outside of a procedure");
+    let error = from_sexp("(if true\n  (then\n    (procedure p ()\n      (print 1))))").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid syntax tree: 3:5: procedures can only be defined at the top level of the program"
+    );
+    let nested = json!([{"type": "procedure", "name": "p", "parameters": [], "body": [
+        {"type": "procedure", "name": "q", "parameters": [], "body": []}
+    ]}]);
+    assert!(from_json(&nested).unwrap_err().to_string().contains("top level"));
+    let returns = json!([{"type": "for", "variable": "i", "from": {"type": "int", "value": 1},
+        "to": {"type": "int", "value": 2}, "body": [{"type": "return"}]}]);
+    assert!(from_json(&returns).unwrap_err().to_string().contains("outside of a procedure"));
+    let statements = parse_source(
+        "procedure p() do\n  while true do\n    return;\n  end while;\nend procedure;",
+        Dialect::Extended,
+    );
+    assert_eq!(from_json(&to_json(&statements)), Ok(statements.clone()));
+    assert_eq!(from_sexp(&to_sexp(&statements)), Ok(statements));
+}
+
+#[test]
+fn names_that_are_extended_keywords_survive_s_expressions() {
+    let source = "var if : int := 1;\nvar true : bool;\nread true;\nfor while in 1..if do print while; end for;\nassert (!true | if = 2);";
+    let statements = parse_source(source, Dialect::Standard);
+    let sexp = to_sexp(&statements);
+    assert!(
+        sexp.starts_with("(var (identifier \"if\") int 1)\n(var (identifier \"true\") bool)\n"),
+        "{}",
+        sexp
+    );
+    assert_eq!(from_sexp(&sexp), Ok(statements));
+    assert_eq!(
+        from_sexp("(print (identifier \"of\"))\n(print true)"),
+        Ok(vec![
+            statement(StatementKind::Print(singleton(id("of")))),
+            statement(StatementKind::Print(singleton(OperandKind::Bool(true)))),
+        ])
+    );
+    assert!(from_sexp("(read (identifier of))").is_err());
+}
+
+#[test]
+fn if_statements_nest_with_for_loops() {
+    let source = "if x < 3 then\n  for i in 1..x do\n    if i = 2 then print i; end if;\n  end for;\nelse\n  print \"big\";\n  if !b then else ; end if;\nend if;";
+    let statements = parse_source(source, Dialect::Extended);
+    let inner_if = statement(StatementKind::If(
+        binary(id("i"), BinaryOperator::Equals, int(2)),
+        vec![statement(StatementKind::Print(singleton(id("i"))))],
+        vec![],
+    ));
+    assert_eq!(
+        statements,
+        vec![
+            statement(StatementKind::If(
+                binary(id("x"), BinaryOperator::LessThan, int(3)),
+                vec![
+                    statement(StatementKind::For(
+                        "i".into(),
+                        singleton(int(1)),
+                        singleton(id("x")),
+                        vec![inner_if],
+                    )),
+                ],
+                vec![
+                    statement(StatementKind::Print(singleton(string("big")))),
+                    statement(StatementKind::If(unary(UnaryOperator::Not, id("b")), vec![], vec![])),
+                ],
+            )),
+        ]
+    );
+    assert_eq!((statements[0].span.start, statements[0].span.end), (0, source.len() - 1));
+    assert_round_trips(&statements);
+    assert!(to_sexp(&statements).starts_with("(if (< x 3)\n  (then\n    (for i 1 x\n"));
+
+    let source = "else\nif x then print 1;\nend for;\nif then print 2; else print 3; else print 4; end if;\nif 1 then\n  print 5 +;\nelse print 6; end if;\nend if;\nif y then";
+    let (errors, statements) = parse_errors(source, Dialect::Extended);
+    assert_eq!(
+        errors,
+        vec![
+            (ParseErrorKind::UnmatchedElse, 1),
+            (ParseErrorKind::Expected("'if'", Token::KeyWord(KeyWord::For)), 3),
+            (ParseErrorKind::Expected("an operand", Token::KeyWord(KeyWord::Then)), 4),
+            (ParseErrorKind::UnmatchedElse, 4),
+            (ParseErrorKind::Expected("an operand", Token::Semicolon), 6),
+            (ParseErrorKind::UnmatchedEnd, 8),
+            (ParseErrorKind::UnclosedIf, 9),
+        ]
+    );
+    // The rest of the if statement is parsed after an error in its then branch.
+    assert_eq!(
+        statements,
+        vec![
+            statement(StatementKind::If(
+                singleton(int(1)),
+                vec![],
+                vec![statement(StatementKind::Print(singleton(int(6))))],
+            )),
+        ]
+    );
+}
+
+#[test]
+fn while_loops_are_parsed() {
+    let source = "while i < n do\n  i := i + 1;\n  if i = 3 then while !done do read done; end while; end if;\nend while;";
+    let statements = parse_source(source, Dialect::Extended);
+    let inner_while = statement(StatementKind::While(
+        unary(UnaryOperator::Not, id("done")),
+        vec![statement(StatementKind::Read("done".into()))],
+    ));
+    assert_eq!(
+        statements,
+        vec![
+            statement(StatementKind::While(
+                binary(id("i"), BinaryOperator::LessThan, id("n")),
+                vec![
+                    statement(StatementKind::Assignment(
+                        "i".into(),
+                        binary(id("i"), BinaryOperator::Plus, int(1)),
+                    )),
+                    statement(StatementKind::If(
+                        binary(id("i"), BinaryOperator::Equals, int(3)),
+                        vec![inner_while],
+                        vec![],
+                    )),
+                ],
+            )),
+        ]
+    );
+    assert_eq!((statements[0].span.start, statements[0].span.end), (0, source.len() - 1));
+    assert_round_trips(&statements);
+    assert!(to_sexp(&statements).starts_with("(while (< i n)\n  (:= i (+ i 1))\n"));
+
+    let source = "while x do print 1; end for;\nwhile y; print 2; end while;\nend while;\nwhile z do";
+    let (errors, statements) = parse_errors(source, Dialect::Extended);
+    assert_eq!(
+        errors,
+        vec![
+            (ParseErrorKind::Expected("'while'", Token::KeyWord(KeyWord::For)), 1),
+            (ParseErrorKind::Expected("'do'", Token::Semicolon), 2),
+            (ParseErrorKind::UnmatchedEnd, 3),
+            (ParseErrorKind::UnclosedWhile, 4),
+        ]
+    );
+    assert_eq!(statements, vec![]);
+}
+
+#[test]
+fn procedures_and_calls_are_parsed() {
+    let source = "function fact(n : int) : int do\n  if n < 2 then return 1; end if;\n  return n * fact(n - 1);\nend function;\nprocedure greet(name : string, times : int) do\n  print name;\n  return;\nend procedure;\ngreet(\"x\", fact((3)));\nprint !ready() & answer(1 + 2, \"a\") = 42;";
+    let statements = parse_source(source, Dialect::Extended);
+    let call = |name: &str, arguments: Vec<Expression>| OperandKind::Call(name.into(), arguments);
+    let parameter = |name: &str, typ| Parameter { name: name.into(), typ };
+    assert_eq!(
+        statements,
+        vec![
+            statement(StatementKind::Procedure(
+                "fact".into(),
+                vec![parameter("n", Type::Int)],
+                Some(Type::Int),
+                vec![
+                    statement(StatementKind::If(
+                        binary(id("n"), BinaryOperator::LessThan, int(2)),
+                        vec![statement(StatementKind::Return(Some(singleton(int(1)))))],
+                        vec![],
+                    )),
+                    statement(StatementKind::Return(Some(binary(
+                        id("n"),
+                        BinaryOperator::Multiply,
+                        call("fact", vec![binary(id("n"), BinaryOperator::Minus, int(1))]),
+                    )))),
+                ],
+            )),
+            statement(StatementKind::Procedure(
+                "greet".into(),
+                vec![parameter("name", Type::Str), parameter("times", Type::Int)],
+                None,
+                vec![
+                    statement(StatementKind::Print(singleton(id("name")))),
+                    statement(StatementKind::Return(None)),
+                ],
+            )),
+            statement(StatementKind::Call(
+                "greet".into(),
+                vec![singleton(string("x")), singleton(call("fact", vec![singleton(int(3))]))],
+            )),
+            statement(StatementKind::Print(binary(
+                expr(unary(UnaryOperator::Not, call("ready", vec![]))),
+                BinaryOperator::And,
+                expr(binary(
+                    call(
+                        "answer",
+                        vec![
+                            binary(int(1), BinaryOperator::Plus, int(2)),
+                            singleton(string("a")),
+                        ],
+                    ),
+                    BinaryOperator::Equals,
+                    int(42),
+                )),
+            ))),
+        ]
+    );
+    assert_round_trips(&statements);
+    assert!(to_sexp(&statements).starts_with("(function fact ((n int)) int\n"));
+
+    let source = "procedure p(a int) do print a; end procedure;\nfunction f() do end function;\nreturn 1;\nfor i in 1..2 do procedure q() do end procedure; end for;\np(1, );\np(1) + 2;\nprocedure r() do end function;\nfunction g(): int do";
+    let (errors, statements) = parse_errors(source, Dialect::Extended);
+    assert_eq!(
+        errors,
+        vec![
+            (ParseErrorKind::Expected("':'", Token::KeyWord(KeyWord::Int)), 1),
+            (ParseErrorKind::Expected("':'", Token::KeyWord(KeyWord::Do)), 2),
+            (ParseErrorKind::ReturnOutsideProcedure, 3),
+            (ParseErrorKind::NestedProcedure, 4),
+            (ParseErrorKind::Expected("an operand", Token::Bracket(Direction::Right)), 5),
+            (ParseErrorKind::Expected("';'", Token::Operator(Operator::Plus)), 6),
+            (ParseErrorKind::Expected("'procedure'", Token::KeyWord(KeyWord::Function)), 7),
+            (ParseErrorKind::UnclosedProcedure, 8),
+        ]
+    );
+    // Only the nested procedure is dropped from the for loop.
+    assert_eq!(
+        statements,
+        vec![
+            statement(StatementKind::For(
+                "i".into(),
+                singleton(int(1)),
+                singleton(int(2)),
+                vec![],
+            )),
+        ]
+    );
+}
+
+#[test]
+fn arrays_are_parsed() {
+    let source = "var a : array [3] of int;\na[0] := 1;\na[i + 1] := a[(i)] * 2;\nvar b : array [3] of int := a;";
+    let statements = parse_source(source, Dialect::Extended);
+    let array = Type::Array(3, Box::new(Type::Int));
+    assert_eq!(
+        statements,
+        vec![
+            statement(StatementKind::Declaration("a".into(), array.clone(), None, vec![])),
+            statement(StatementKind::IndexedAssignment("a".into(), singleton(int(0)), singleton(int(1)))),
+            statement(StatementKind::IndexedAssignment(
+                "a".into(),
+                binary(id("i"), BinaryOperator::Plus, int(1)),
+                binary(
+                    OperandKind::Index("a".into(), Box::new(singleton(id("i")))),
+                    BinaryOperator::Multiply,
+                    int(2),
+                ),
+            )),
+            statement(StatementKind::Declaration("b".into(), array, Some(singleton(id("a"))), vec![])),
+        ]
+    );
+    assert_round_trips(&statements);
+    assert!(to_sexp(&statements).starts_with("(var a (array 3 int))\n(:= (index a 0) 1)\n"));
+
+    let source = "var a : array [x] of int;\nvar b : array [2] int;\nvar c : array [2] of array;\nvar d : array [2] of int bool;\na[1] + 2;\na[1 := 2;\nvar e : array [100000000000] of int;";
+    let (errors, _) = parse_errors(source, Dialect::Extended);
+    assert_eq!(
+        errors,
+        vec![
+            (ParseErrorKind::Expected("an array size", Token::Identifier("x".into())), 1),
+            (ParseErrorKind::Expected("'of'", Token::KeyWord(KeyWord::Int)), 2),
+            (ParseErrorKind::Expected("'int', 'string' or 'bool'", Token::KeyWord(KeyWord::Array)), 3),
+            (ParseErrorKind::Expected("':=' or ';'", Token::KeyWord(KeyWord::Bool)), 4),
+            (ParseErrorKind::Expected("':='", Token::Semicolon), 5),
+            (ParseErrorKind::Expected("']'", Token::Assignment), 6),
+            (ParseErrorKind::ArrayTooLarge, 7),
+        ]
+    );
+    let error = from_sexp("(var e (array 1048577 int))").unwrap_err();
+    assert_eq!(error.to_string(), "invalid syntax tree: 1:8: arrays can have at most 1048576 elements");
+    let too_large = json!([{"type": "declaration", "name": "e",
+        "var_type": {"size": 100000000000u64, "element": "int"}}]);
+    assert!(from_json(&too_large).unwrap_err().to_string().contains("at most 1048576 elements"));
+}
+
+#[test]
+fn boolean_literals_are_parsed() {
+    let source = "var b : bool := true;\nprint !false & (b = true);";
+    let statements = parse_source(source, Dialect::Extended);
+    assert_eq!(
+        statements,
+        vec![
+            statement(StatementKind::Declaration(
+                "b".into(),
+                Type::Bool,
+                Some(singleton(OperandKind::Bool(true))),
+                vec![],
+            )),
+            statement(StatementKind::Print(binary(
+                expr(unary(UnaryOperator::Not, OperandKind::Bool(false))),
+                BinaryOperator::And,
+                expr(binary(id("b"), BinaryOperator::Equals, OperandKind::Bool(true))),
+            ))),
+        ]
+    );
+    assert_round_trips(&statements);
+    // Only the extended dialect has the literals.
+    assert_eq!(
+        parse_source("print true;", Dialect::Standard),
+        vec![statement(StatementKind::Print(singleton(id("true"))))]
+    );
+}
@@ -0,0 +1,187 @@
+// Traversals of the syntax tree. A ´Visitor´ looks at the nodes of a tree and a ´Folder´ takes
+// the tree apart and builds a new one from it. Both have a method for each kind of node whose
+// default implementation calls the matching ´walk´ function, which goes through the children of
+// the node. Implementations only override the methods of the nodes they are interested in and
+// call the ´walk´ function themselves if they still want the children to be visited.
+
+use super::{Expression, ExpressionKind, Operand, OperandKind, Statement, StatementKind};
+
+/// Goes through a syntax tree without changing it.
+pub trait Visitor {
+    fn visit_statement(&mut self, statement: &Statement) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        walk_expression(self, expression);
+    }
+
+    fn visit_operand(&mut self, operand: &Operand) {
+        walk_operand(self, operand);
+    }
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &Statement) {
+    match statement.kind {
+        StatementKind::Declaration(_, _, Some(ref expression), _)
+        | StatementKind::Assignment(_, ref expression)
+        | StatementKind::Print(ref expression)
+        | StatementKind::Assert(ref expression)
+        | StatementKind::Return(Some(ref expression)) => visitor.visit_expression(expression),
+        StatementKind::IndexedAssignment(_, ref index, ref expression) => {
+            visitor.visit_expression(index);
+            visitor.visit_expression(expression);
+        }
+        StatementKind::For(_, ref from, ref to, ref body) => {
+            visitor.visit_expression(from);
+            visitor.visit_expression(to);
+            for statement in body {
+                visitor.visit_statement(statement);
+            }
+        }
+        StatementKind::If(ref condition, ref then, ref otherwise) => {
+            visitor.visit_expression(condition);
+            for statement in then.iter().chain(otherwise) {
+                visitor.visit_statement(statement);
+            }
+        }
+        StatementKind::While(ref condition, ref body) => {
+            visitor.visit_expression(condition);
+            for statement in body {
+                visitor.visit_statement(statement);
+            }
+        }
+        StatementKind::Procedure(_, _, _, ref body) => {
+            for statement in body {
+                visitor.visit_statement(statement);
+            }
+        }
+        StatementKind::Call(_, ref arguments) => {
+            for argument in arguments {
+                visitor.visit_expression(argument);
+            }
+        }
+        StatementKind::Declaration(_, _, None, _) | StatementKind::Return(None) | StatementKind::Read(_) => {}
+    }
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &Expression) {
+    match expression.kind {
+        ExpressionKind::Binary(ref left, _, ref right) => {
+            visitor.visit_operand(left);
+            visitor.visit_operand(right);
+        }
+        ExpressionKind::Unary(_, ref operand) | ExpressionKind::Singleton(ref operand) => {
+            visitor.visit_operand(operand)
+        }
+    }
+}
+
+pub fn walk_operand<V: Visitor + ?Sized>(visitor: &mut V, operand: &Operand) {
+    match operand.kind {
+        OperandKind::Expr(ref expression) | OperandKind::Index(_, ref expression) => {
+            visitor.visit_expression(expression)
+        }
+        OperandKind::Call(_, ref arguments) => {
+            for argument in arguments {
+                visitor.visit_expression(argument);
+            }
+        }
+        OperandKind::Int(_)
+        | OperandKind::StringLiteral(_)
+        | OperandKind::Bool(_)
+        | OperandKind::Identifier(_) => {}
+    }
+}
+
+/// Rewrites a syntax tree. The default methods rebuild the nodes as they were, keeping their IDs
+/// and spans.
+pub trait Folder {
+    fn fold_statement(&mut self, statement: Statement) -> Statement {
+        walk_fold_statement(self, statement)
+    }
+
+    fn fold_expression(&mut self, expression: Expression) -> Expression {
+        walk_fold_expression(self, expression)
+    }
+
+    fn fold_operand(&mut self, operand: Operand) -> Operand {
+        walk_fold_operand(self, operand)
+    }
+}
+
+pub fn walk_fold_statement<F: Folder + ?Sized>(folder: &mut F, statement: Statement) -> Statement {
+    let kind = match statement.kind {
+        StatementKind::Declaration(identifier, typ, expression, docs) => StatementKind::Declaration(
+            identifier,
+            typ,
+            expression.map(|e| folder.fold_expression(e)),
+            docs,
+        ),
+        StatementKind::Assignment(identifier, expression) => {
+            StatementKind::Assignment(identifier, folder.fold_expression(expression))
+        }
+        StatementKind::IndexedAssignment(identifier, index, expression) => {
+            let index = folder.fold_expression(index);
+            StatementKind::IndexedAssignment(identifier, index, folder.fold_expression(expression))
+        }
+        StatementKind::For(identifier, from, to, body) => {
+            let from = folder.fold_expression(from);
+            let to = folder.fold_expression(to);
+            let body = body.into_iter().map(|s| folder.fold_statement(s)).collect();
+            StatementKind::For(identifier, from, to, body)
+        }
+        StatementKind::If(condition, then, otherwise) => {
+            let condition = folder.fold_expression(condition);
+            let then = then.into_iter().map(|s| folder.fold_statement(s)).collect();
+            let otherwise = otherwise.into_iter().map(|s| folder.fold_statement(s)).collect();
+            StatementKind::If(condition, then, otherwise)
+        }
+        StatementKind::While(condition, body) => {
+            let condition = folder.fold_expression(condition);
+            let body = body.into_iter().map(|s| folder.fold_statement(s)).collect();
+            StatementKind::While(condition, body)
+        }
+        StatementKind::Procedure(name, parameters, return_type, body) => {
+            let body = body.into_iter().map(|s| folder.fold_statement(s)).collect();
+            StatementKind::Procedure(name, parameters, return_type, body)
+        }
+        StatementKind::Call(name, arguments) => {
+            StatementKind::Call(name, arguments.into_iter().map(|e| folder.fold_expression(e)).collect())
+        }
+        StatementKind::Return(expression) => StatementKind::Return(expression.map(|e| folder.fold_expression(e))),
+        StatementKind::Read(identifier) => StatementKind::Read(identifier),
+        StatementKind::Print(expression) => StatementKind::Print(folder.fold_expression(expression)),
+        StatementKind::Assert(expression) => StatementKind::Assert(folder.fold_expression(expression)),
+    };
+    Statement { kind, ..statement }
+}
+
+pub fn walk_fold_expression<F: Folder + ?Sized>(folder: &mut F, expression: Expression) -> Expression {
+    let kind = match expression.kind {
+        ExpressionKind::Binary(left, operator, right) => {
+            let left = folder.fold_operand(left);
+            let right = folder.fold_operand(right);
+            ExpressionKind::Binary(left, operator, right)
+        }
+        ExpressionKind::Unary(operator, operand) => {
+            ExpressionKind::Unary(operator, folder.fold_operand(operand))
+        }
+        ExpressionKind::Singleton(operand) => ExpressionKind::Singleton(folder.fold_operand(operand)),
+    };
+    Expression { kind, ..expression }
+}
+
+pub fn walk_fold_operand<F: Folder + ?Sized>(folder: &mut F, operand: Operand) -> Operand {
+    let kind = match operand.kind {
+        OperandKind::Expr(expression) => OperandKind::Expr(Box::new(folder.fold_expression(*expression))),
+        OperandKind::Index(identifier, index) => {
+            OperandKind::Index(identifier, Box::new(folder.fold_expression(*index)))
+        }
+        OperandKind::Call(name, arguments) => {
+            OperandKind::Call(name, arguments.into_iter().map(|e| folder.fold_expression(e)).collect())
+        }
+        kind => kind,
+    };
+    Operand { kind, ..operand }
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use unicode_xid::UnicodeXID;
+
+use super::KeyWord;
+
+/// The variants of Mini-PL the scanner knows about.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dialect {
+    /// Mini-PL as specified in the course.
+    Standard,
+    /// Standard Mini-PL with the control flow, procedure, array, boolean literal and raw string extensions.
+    Extended,
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "standard" => Ok(Dialect::Standard),
+            "extended" => Ok(Dialect::Extended),
+            _ => Err(format!("unknown dialect {}", s)),
+        }
+    }
+}
+
+/// Settings that control what the scanner accepts and what it produces.
+#[derive(Clone, Debug)]
+pub struct LexerConfig {
+    pub dialect: Dialect,
+    /// Words that are scanned as keywords instead of identifiers.
+    /// The words need to be in lower case if keywords are case insensitive.
+    pub keywords: HashMap<&'static str, KeyWord>,
+    /// If false, keywords are recognized regardless of case, e.g. VAR and Var are both ´KeyWord::Var´.
+    /// Identifiers are always case sensitive.
+    pub case_sensitive_keywords: bool,
+    /// Characters that can start an identifier. By default the characters with the Unicode
+    /// XID_Start property.
+    pub identifier_start: fn(char) -> bool,
+    /// Characters that can appear in an identifier after the first one. By default the characters
+    /// with the Unicode XID_Continue property, which include digits and '_'.
+    pub identifier_continue: fn(char) -> bool,
+    /// Whether identifiers are converted to Unicode normalization form C so that identifiers that
+    /// look the same are the same, e.g. ´é´ written as one character or as e and a combining accent.
+    pub normalize_identifiers: bool,
+    /// Whether string literals can contain line breaks.
+    pub multiline_strings: bool,
+    /// Whether raw string literals like r"C:\path" and r#"a "quoted" word"# are recognized.
+    pub raw_strings: bool,
+    /// Whether whitespace and comments are kept as trivia attached to the tokens.
+    pub preserve_trivia: bool,
+}
+
+impl LexerConfig {
+    /// Creates the configuration of the given dialect.
+    pub fn new(dialect: Dialect) -> Self {
+        let mut keywords = HashMap::new();
+        keywords.insert("var", KeyWord::Var);
+        keywords.insert("end", KeyWord::End);
+        keywords.insert("for", KeyWord::For);
+        keywords.insert("in", KeyWord::In);
+        keywords.insert("do", KeyWord::Do);
+        keywords.insert("read", KeyWord::Read);
+        keywords.insert("print", KeyWord::Print);
+        keywords.insert("int", KeyWord::Int);
+        keywords.insert("string", KeyWord::String);
+        keywords.insert("bool", KeyWord::Bool);
+        keywords.insert("assert", KeyWord::Assert);
+        if dialect == Dialect::Extended {
+            keywords.insert("if", KeyWord::If);
+            keywords.insert("then", KeyWord::Then);
+            keywords.insert("else", KeyWord::Else);
+            keywords.insert("while", KeyWord::While);
+            keywords.insert("true", KeyWord::True);
+            keywords.insert("false", KeyWord::False);
+            keywords.insert("procedure", KeyWord::Procedure);
+            keywords.insert("function", KeyWord::Function);
+            keywords.insert("return", KeyWord::Return);
+            keywords.insert("array", KeyWord::Array);
+            keywords.insert("of", KeyWord::Of);
+        }
+        LexerConfig {
+            dialect,
+            keywords,
+            case_sensitive_keywords: true,
+            identifier_start: UnicodeXID::is_xid_start,
+            identifier_continue: UnicodeXID::is_xid_continue,
+            normalize_identifiers: true,
+            multiline_strings: true,
+            raw_strings: dialect == Dialect::Extended,
+            preserve_trivia: false,
+        }
+    }
+
+    /// Returns the keyword ´word´ stands for or None if it is not a keyword.
+    pub fn keyword(&self, word: &str) -> Option<KeyWord> {
+        if self.case_sensitive_keywords {
+            self.keywords.get(word).cloned()
+        } else {
+            self.keywords.get(&*word.to_lowercase()).cloned()
+        }
+    }
+}
+
+impl Default for LexerConfig {
+    fn default() -> Self {
+        LexerConfig::new(Dialect::Standard)
+    }
+}
@@ -0,0 +1,100 @@
+use std::fmt;
+
+use util::Span;
+
+/// A problem found in the source text during scanning.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub span: Span,
+}
+
+/// All the different kinds of lexical errors the scanner can report.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LexErrorKind {
+    /// A backslash in a string literal was followed by a character that doesn't start an escape.
+    UnsupportedEscape(char),
+    /// ´\x´ was not followed by any hexadecimal digits.
+    MissingHexDigits,
+    /// A hexadecimal escape had more digits than fit into a code point.
+    HexEscapeTooLong,
+    /// An octal escape did not fit into a byte.
+    OctalEscapeOutOfRange,
+    /// A ´\u´ or ´\U´ escape contained a character that isn't a hexadecimal digit.
+    InvalidUnicodeDigit(char),
+    /// ´\u{}´ had no digits between the braces.
+    EmptyUnicodeEscape,
+    /// The string literal ended before the closing brace of a ´\u{...}´ escape.
+    UnclosedUnicodeEscape,
+    /// A unicode escape named a value that is not a unicode scalar value.
+    InvalidCodePoint(u32),
+    /// A number literal could not be parsed.
+    InvalidNumber(String),
+    /// A radix prefix like 0x was not followed by any digits.
+    MissingDigits,
+    /// A number literal had a character that is not a digit in the literal's radix.
+    InvalidDigit(char, u32),
+    /// A digit separator '_' was at the start or the end of the digits or next to another separator.
+    MisplacedSeparator,
+    /// A single '.' was found where a range ".." was expected.
+    IncompleteRange,
+    /// The input ended inside a string literal.
+    UnterminatedString,
+    /// The input ended inside a block comment. Contains the number of comments left open.
+    UnterminatedBlockComment(usize),
+    /// A line break inside a string literal when multi-line strings are not allowed.
+    NewlineInString,
+    /// The '#'s starting a raw string literal were not followed by a quote.
+    InvalidRawStringStart,
+    /// The input ended right after a backslash or in the middle of an escape sequence.
+    DanglingEscape,
+    /// The input contained bytes that are not valid UTF-8.
+    InvalidUtf8,
+    /// A character that can't start any token.
+    IllegalCharacter(char),
+}
+
+impl fmt::Display for LexErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::LexErrorKind::*;
+        match *self {
+            UnsupportedEscape(c) => write!(f, "escape \\{} is not supported", c),
+            MissingHexDigits => write!(f, "at least one hexadecimal digit is needed after \\x"),
+            HexEscapeTooLong => write!(f, "hex escape sequence is too long to be a unicode codepoint"),
+            OctalEscapeOutOfRange => write!(f, "octal escape sequence does not fit into a byte"),
+            InvalidUnicodeDigit(c) => write!(
+                f,
+                "{:?} is not a valid hex digit in a unicode escape sequence",
+                c
+            ),
+            EmptyUnicodeEscape => write!(f, "expected hex digits between the braces of \\u{{}}"),
+            UnclosedUnicodeEscape => write!(f, "unicode escape sequence is missing its closing '}}'"),
+            InvalidCodePoint(n) => write!(f, "{:X} is an invalid unicode codepoint", n),
+            InvalidNumber(ref n) => write!(f, "invalid number literal {}", n),
+            MissingDigits => write!(f, "expected digits after the radix prefix"),
+            InvalidDigit(c, radix) => write!(f, "{:?} is not a valid base {} digit", c, radix),
+            MisplacedSeparator => write!(
+                f,
+                "digit separators '_' are only allowed between two digits"
+            ),
+            IncompleteRange => write!(f, "expected '..' but found a single '.'"),
+            UnterminatedString => write!(f, "unterminated string literal"),
+            UnterminatedBlockComment(depth) => write!(
+                f,
+                "unterminated block comment ({} level(s) of nesting left open)",
+                depth
+            ),
+            NewlineInString => write!(f, "string literals can't span multiple lines"),
+            InvalidRawStringStart => write!(f, "expected '\"' to start the raw string literal"),
+            DanglingEscape => write!(f, "the input ended in the middle of an escape sequence"),
+            InvalidUtf8 => write!(f, "the input is not valid UTF-8"),
+            IllegalCharacter(c) => write!(f, "illegal character {:?}", c),
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.kind)
+    }
+}
@@ -0,0 +1,381 @@
+use std::fmt;
+use std::mem;
+use std::ops::Range;
+
+use util::Span;
+
+use super::{LexError, LexerConfig, Position, ScanMode, Scanner, SpannedToken, Trivia};
+
+/// Keeps the tokens of a source text up to date while the text is being edited.
+/// The scanner state is saved at the start of every line. An edit is re-lexed starting from the
+/// line it begins on and scanning stops as soon as the scanner is back in a state it was in
+/// before the edit, after which the old tokens are reused with their positions shifted.
+pub struct IncrementalScanner {
+    scanner: Scanner,
+    source: String,
+    tokens: Vec<SpannedToken>,
+    errors: Vec<LexError>,
+    /// The state of the scanner at the start of each line, in the order of the lines.
+    checkpoints: Vec<Checkpoint>,
+}
+
+/// The change an edit caused to the token list. The tokens ´start..start + removed´ of the old
+/// list were replaced with ´inserted´. Tokens after the replaced ones only had their spans shifted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenDelta {
+    pub start: usize,
+    pub removed: usize,
+    pub inserted: Vec<SpannedToken>,
+}
+
+/// The reason an edit could not be applied to the source.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EditError {
+    /// The range ends before it starts.
+    Reversed(Range<usize>),
+    /// The range goes past the end of the source, which is this many bytes long.
+    OutOfBounds(Range<usize>, usize),
+    /// The offset is inside of a character instead of between two.
+    NotCharBoundary(usize),
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EditError::Reversed(ref range) => write!(f, "the edit range {:?} ends before it starts", range),
+            EditError::OutOfBounds(ref range, len) => write!(
+                f,
+                "the edit range {:?} goes past the end of the source of {} bytes",
+                range, len
+            ),
+            EditError::NotCharBoundary(offset) => write!(f, "the edit offset {} is inside of a character", offset),
+        }
+    }
+}
+
+/// Everything needed to continue scanning from the start of a line.
+#[derive(Clone, Debug)]
+struct Checkpoint {
+    position: Position,
+    scan_mode: ScanMode,
+    buffer: String,
+    escape_buffer: String,
+    block_comment_counter: usize,
+    comment: String,
+    raw_hashes: usize,
+    closing_hashes: usize,
+    token_start: Position,
+    escape_start: Position,
+    text: String,
+    text_start: Position,
+    trivia: Vec<Trivia>,
+    /// Number of tokens emitted before this line.
+    tokens: usize,
+    /// Number of errors found before this line.
+    errors: usize,
+}
+
+/// Maps positions from before an edit to where they are after it.
+struct Shift {
+    /// Where the replaced text started.
+    start: usize,
+    /// The end of the replaced text before the edit.
+    old_end: Position,
+    /// The end of the inserted text after the edit.
+    new_end: Position,
+}
+
+impl Shift {
+    /// Returns None for positions that were inside the replaced text.
+    fn position(&self, p: Position) -> Option<Position> {
+        if p.offset < self.start {
+            Some(p)
+        } else if p.offset < self.old_end.offset {
+            None
+        } else if p.line == self.old_end.line {
+            Some(Position {
+                offset: p.offset + self.new_end.offset - self.old_end.offset,
+                line: self.new_end.line,
+                column: p.column + self.new_end.column - self.old_end.column,
+            })
+        } else {
+            Some(Position {
+                offset: p.offset + self.new_end.offset - self.old_end.offset,
+                line: p.line + self.new_end.line - self.old_end.line,
+                column: p.column,
+            })
+        }
+    }
+
+    fn span(&self, span: Span) -> Span {
+        let start = Position {
+            offset: span.start,
+            line: span.line,
+            column: span.column,
+        };
+        let start = self.position(start).unwrap_or(self.new_end);
+        // Spans end right before the byte at ´end´ so a span ending where the replaced text
+        // starts is not affected by the edit.
+        let end = if span.end <= self.start {
+            span.end
+        } else if span.end >= self.old_end.offset {
+            span.end + self.new_end.offset - self.old_end.offset
+        } else {
+            self.new_end.offset
+        };
+        Span {
+            start: start.offset,
+            end,
+            line: start.line,
+            column: start.column,
+        }
+    }
+
+    fn trivia(&self, trivia: &Trivia) -> Trivia {
+        Trivia {
+            span: self.span(trivia.span),
+            ..trivia.clone()
+        }
+    }
+
+    fn checkpoint(&self, checkpoint: &Checkpoint) -> Checkpoint {
+        let shift = |p| self.position(p).unwrap_or(p);
+        Checkpoint {
+            position: shift(checkpoint.position),
+            token_start: shift(checkpoint.token_start),
+            escape_start: shift(checkpoint.escape_start),
+            text_start: shift(checkpoint.text_start),
+            trivia: checkpoint.trivia.iter().map(|t| self.trivia(t)).collect(),
+            ..checkpoint.clone()
+        }
+    }
+
+    /// Checks if scanning from the new checkpoint gives the same tokens as scanning from the
+    /// old checkpoint did, apart from the shift.
+    fn same_state(&self, new: &Checkpoint, old: &Checkpoint) -> bool {
+        let same = |new, old| self.position(old) == Some(new);
+        new.scan_mode == old.scan_mode && new.buffer == old.buffer
+            && new.escape_buffer == old.escape_buffer
+            && new.block_comment_counter == old.block_comment_counter
+            && new.comment == old.comment
+            && new.raw_hashes == old.raw_hashes
+            && new.closing_hashes == old.closing_hashes
+            && same(new.token_start, old.token_start)
+            && same(new.escape_start, old.escape_start) && new.text == old.text
+            && same(new.text_start, old.text_start)
+            && new.trivia == old.trivia.iter().map(|t| self.trivia(t)).collect::<Vec<_>>()
+    }
+}
+
+impl IncrementalScanner {
+    /// Scans ´source´ completely.
+    pub fn new(config: LexerConfig, source: &str) -> Self {
+        let mut scanner = Scanner::with_config(config);
+        let mut tokens = Vec::new();
+        let mut checkpoints = vec![Checkpoint::new(&scanner, 0, 0)];
+        for c in source.chars() {
+            scanner.scan_char(c);
+            scanner.flush(&mut tokens);
+            if c == '\n' {
+                checkpoints.push(Checkpoint::new(&scanner, tokens.len(), scanner.errors.len()));
+            }
+        }
+        scanner.finish(&mut tokens);
+        IncrementalScanner {
+            errors: mem::take(&mut scanner.errors),
+            scanner,
+            source: source.to_string(),
+            tokens,
+            checkpoints,
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn tokens(&self) -> &[SpannedToken] {
+        &self.tokens
+    }
+
+    pub fn errors(&self) -> &[LexError] {
+        &self.errors
+    }
+
+    /// Replaces the bytes in ´range´ of the source with ´text´ and re-lexes the affected part.
+    /// The source is left as it was if the range doesn't select whole characters of it.
+    pub fn edit(&mut self, range: Range<usize>, text: &str) -> Result<TokenDelta, EditError> {
+        if range.end < range.start {
+            return Err(EditError::Reversed(range));
+        }
+        if range.end > self.source.len() {
+            return Err(EditError::OutOfBounds(range, self.source.len()));
+        }
+        for &offset in &[range.start, range.end] {
+            if !self.source.is_char_boundary(offset) {
+                return Err(EditError::NotCharBoundary(offset));
+            }
+        }
+        // The last line that starts before the edit is not affected by it.
+        let first = self.checkpoints
+            .iter()
+            .rposition(|c| c.position.offset <= range.start)
+            .unwrap();
+        let line_start = self.checkpoints[first].position;
+        let old_end = advance(line_start, &self.source[line_start.offset..range.end]);
+        self.source = format!(
+            "{}{}{}",
+            &self.source[..range.start],
+            text,
+            &self.source[range.end..]
+        );
+        let new_end = advance(line_start, &self.source[line_start.offset..range.start + text.len()]);
+        let shift = Shift {
+            start: range.start,
+            old_end,
+            new_end,
+        };
+
+        let start = self.checkpoints[first].clone();
+        start.restore(&mut self.scanner);
+        let mut inserted = Vec::new();
+        let mut new_checkpoints = Vec::new();
+        let mut resumed = None;
+        for c in self.source[line_start.offset..].chars() {
+            self.scanner.scan_char(c);
+            self.scanner.flush(&mut inserted);
+            if c != '\n' {
+                continue;
+            }
+            let checkpoint = Checkpoint::new(
+                &self.scanner,
+                start.tokens + inserted.len(),
+                start.errors + self.scanner.errors.len(),
+            );
+            let position = checkpoint.position.offset;
+            if position >= new_end.offset {
+                let old_position = position + old_end.offset - new_end.offset;
+                if let Ok(i) = self.checkpoints
+                    .binary_search_by_key(&old_position, |c| c.position.offset)
+                {
+                    if shift.same_state(&checkpoint, &self.checkpoints[i]) {
+                        resumed = Some(i);
+                        break;
+                    }
+                }
+            }
+            new_checkpoints.push(checkpoint);
+        }
+        let (removed, removed_errors, old_checkpoints) = match resumed {
+            Some(i) => {
+                let old = &self.checkpoints[i];
+                (
+                    old.tokens - start.tokens,
+                    old.errors - start.errors,
+                    self.checkpoints.split_off(i),
+                )
+            }
+            // The edit affected everything up to the end of the input.
+            None => {
+                self.scanner.finish(&mut inserted);
+                (
+                    self.tokens.len() - start.tokens,
+                    self.errors.len() - start.errors,
+                    Vec::new(),
+                )
+            }
+        };
+        let new_errors = mem::take(&mut self.scanner.errors);
+
+        let token_range = start.tokens..start.tokens + removed;
+        let after: Vec<SpannedToken> = self.tokens
+            .split_off(token_range.end)
+            .into_iter()
+            .map(|mut t| {
+                t.span = shift.span(t.span);
+                for trivia in &mut t.leading_trivia {
+                    *trivia = shift.trivia(trivia);
+                }
+                t
+            })
+            .collect();
+        self.tokens.truncate(token_range.start);
+        self.tokens.extend(inserted.iter().cloned());
+        self.tokens.extend(after);
+
+        let error_range = start.errors..start.errors + removed_errors;
+        let after: Vec<LexError> = self.errors
+            .split_off(error_range.end)
+            .into_iter()
+            .map(|mut e| {
+                e.span = shift.span(e.span);
+                e
+            })
+            .collect();
+        let error_count = new_errors.len();
+        self.errors.truncate(error_range.start);
+        self.errors.extend(new_errors);
+        self.errors.extend(after);
+
+        self.checkpoints.truncate(first + 1);
+        self.checkpoints.extend(new_checkpoints);
+        for old in old_checkpoints {
+            let mut checkpoint = shift.checkpoint(&old);
+            checkpoint.tokens = checkpoint.tokens + inserted.len() - removed;
+            checkpoint.errors = checkpoint.errors + error_count - removed_errors;
+            self.checkpoints.push(checkpoint);
+        }
+
+        Ok(TokenDelta {
+            start: token_range.start,
+            removed,
+            inserted,
+        })
+    }
+}
+
+impl Checkpoint {
+    fn new(scanner: &Scanner, tokens: usize, errors: usize) -> Self {
+        Checkpoint {
+            position: scanner.position,
+            scan_mode: scanner.scan_mode,
+            buffer: scanner.buffer.clone(),
+            escape_buffer: scanner.escape_buffer.clone(),
+            block_comment_counter: scanner.block_comment_counter,
+            comment: scanner.comment.clone(),
+            raw_hashes: scanner.raw_hashes,
+            closing_hashes: scanner.closing_hashes,
+            token_start: scanner.token_start,
+            escape_start: scanner.escape_start,
+            text: scanner.text.clone(),
+            text_start: scanner.text_start,
+            trivia: scanner.trivia.clone(),
+            tokens,
+            errors,
+        }
+    }
+
+    fn restore(&self, scanner: &mut Scanner) {
+        scanner.position = self.position;
+        scanner.scan_mode = self.scan_mode;
+        scanner.buffer = self.buffer.clone();
+        scanner.escape_buffer = self.escape_buffer.clone();
+        scanner.block_comment_counter = self.block_comment_counter;
+        scanner.comment = self.comment.clone();
+        scanner.raw_hashes = self.raw_hashes;
+        scanner.closing_hashes = self.closing_hashes;
+        scanner.token_start = self.token_start;
+        scanner.escape_start = self.escape_start;
+        scanner.text = self.text.clone();
+        scanner.text_start = self.text_start;
+        scanner.trivia = self.trivia.clone();
+        scanner.errors.clear();
+    }
+}
+
+fn advance(mut position: Position, text: &str) -> Position {
+    for c in text.chars() {
+        position.advance(c);
+    }
+    position
+}
@@ -15,14 +15,28 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::mem;
+use std::str;
 use util::Sink;
 use std::char::from_u32;
 
 use num_bigint::BigInt;
+use unicode_normalization::UnicodeNormalization;
 
-use util::Direction;
+use symbol::Symbol;
+use util::{Direction, Span};
 use util::Direction::*;
 
+pub use self::config::{Dialect, LexerConfig};
+pub use self::error::{LexError, LexErrorKind};
+pub use self::incremental::{EditError, IncrementalScanner, TokenDelta};
+pub use self::print::print_tokens;
+
+mod config;
+mod error;
+mod incremental;
+mod print;
 #[cfg(test)]
 mod test;
 
@@ -30,15 +44,65 @@ mod test;
 #[derive(Clone, Debug, PartialEq)]
 pub enum Token {
     Bracket(Direction),
-    Identifier(String),
-    StringLiteral(String),
+    /// ´[´ or ´]´ around the size of an array type or an index.
+    SquareBracket(Direction),
+    Identifier(Symbol),
+    StringLiteral(Symbol),
     Number(BigInt),
     Semicolon,
     Colon,
+    Comma,
     Assignment,
     Operator(Operator),
     KeyWord(KeyWord),
     Range,
+    /// The text of a ´///´ line comment or a ´/** */´ block comment. Doc comments document the
+    /// declaration that follows them. ´////´, ´/***´ and ´/**/´ start ordinary comments.
+    DocComment(Symbol),
+    /// Marks the end of the input. Only emitted when the scanner preserves trivia so that the
+    /// trivia at the end of the source has a token to be attached to.
+    EndOfInput,
+}
+
+/// A token together with the region of the source it was scanned from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+    /// Whitespace and comments between the previous token and this one.
+    /// Only filled in when the scanner preserves trivia.
+    pub leading_trivia: Vec<Trivia>,
+    /// The source text the token was scanned from. Only filled in when the scanner preserves trivia.
+    pub text: String,
+}
+
+/// Source text that doesn't affect the meaning of the program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub span: Span,
+    pub text: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TriviaKind {
+    Whitespace,
+    LineComment,
+    BlockComment,
+    /// Text that was skipped because of a lexical error.
+    Skipped,
+}
+
+/// Rebuilds the source text from tokens scanned with trivia preserved.
+pub fn reconstruct_source(tokens: &[SpannedToken]) -> String {
+    let mut source = String::new();
+    for token in tokens {
+        for trivia in &token.leading_trivia {
+            source.push_str(&trivia.text);
+        }
+        source.push_str(&token.text);
+    }
+    source
 }
 
 /// All the different operators mini-pl has.
@@ -49,8 +113,13 @@ pub enum Operator {
     Multiply,
     Divide,
     LessThan,
+    GreaterThan,
+    LessThanOrEqual,
+    GreaterThanOrEqual,
     Equals,
+    NotEquals,
     And,
+    Or,
     Not,
 }
 
@@ -68,25 +137,110 @@ pub enum KeyWord {
     String,
     Bool,
     Assert,
+    If,
+    Then,
+    Else,
+    While,
+    True,
+    False,
+    Procedure,
+    Function,
+    Return,
+    Array,
+    Of,
 }
+
+/// Parses an integer literal. Literals starting with 0x, 0b or 0o are hexadecimal, binary and octal.
+/// Digits can be separated with single underscores, e.g. 1_000_000 or 0xFF_FF.
+fn parse_number(literal: &str) -> Result<BigInt, LexErrorKind> {
+    let (radix, digits) = if let Some(digits) = literal.strip_prefix("0x") {
+        (16, digits)
+    } else if let Some(digits) = literal.strip_prefix("0b") {
+        (2, digits)
+    } else if let Some(digits) = literal.strip_prefix("0o") {
+        (8, digits)
+    } else {
+        (10, literal)
+    };
+    if digits.is_empty() {
+        return Err(LexErrorKind::MissingDigits);
+    }
+    if digits.starts_with('_') || digits.ends_with('_') || digits.contains("__") {
+        return Err(LexErrorKind::MisplacedSeparator);
+    }
+    if let Some(c) = digits.chars().find(|&c| c != '_' && !c.is_digit(radix)) {
+        return Err(LexErrorKind::InvalidDigit(c, radix));
+    }
+    let digits: String = digits.chars().filter(|&c| c != '_').collect();
+    BigInt::parse_bytes(digits.as_bytes(), radix)
+        .ok_or_else(|| LexErrorKind::InvalidNumber(literal.to_string()))
+}
+
 /// ScanModes can be thought as parts of an finite automaton that handle recognizing different token types.
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum ScanMode {
     Normal,
     StringLiteral,
     Number,
     PossibleComment,
     PossibleAssignment,
+    /// A '<', '>' or '!' (stored in the buffer) that can be the start of a two character operator.
+    PossibleComparison,
     LineComment,
     BlockComment,
     Other,
     Escape,
     Range,
+    /// Between the r and the opening quote of a raw string literal.
+    RawStringStart,
+    RawString,
+    /// After a quote that might end a raw string literal.
+    RawStringEnd,
+}
+
+/// A location in the source text.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Position {
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Position {
+    fn start() -> Self {
+        Position {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    /// Moves the position past the character ´c´.
+    fn advance(&mut self, c: char) {
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+    }
+
+    /// Creates a span that starts from this position and ends at the byte offset ´end´.
+    fn span_to(&self, end: usize) -> Span {
+        Span {
+            start: self.offset,
+            end,
+            line: self.line,
+            column: self.column,
+        }
+    }
 }
 
 /// Scanner is essentially a finite state automaton that takes in a source code as a string and
 pub struct Scanner {
     /// Tokens that have been parsed.
-    tokens: VecDeque<Token>,
+    tokens: VecDeque<SpannedToken>,
     /// Current state of scanning. It used to choose the approriate function to scan for a token.
     scan_mode: ScanMode,
     /// a String used to store previously scanned characters that are needed in the next token.
@@ -95,81 +249,396 @@ pub struct Scanner {
     escape_buffer: String,
 
     block_comment_counter: usize,
+    /// The text of the comment currently being scanned after the opening ´//´ or ´/*´.
+    comment: String,
+    /// Number of '#'s the raw string literal currently being scanned was opened with.
+    raw_hashes: usize,
+    /// Number of '#'s found after a quote in a raw string literal.
+    closing_hashes: usize,
+    /// Location of the character currently being scanned.
+    position: Position,
+    /// Length of the character currently being scanned in bytes.
+    char_len: usize,
+    /// Location where the token currently being scanned started.
+    token_start: Position,
+    /// Location of the backslash that started the escape sequence currently being scanned.
+    escape_start: Position,
+    /// Lexical errors found so far.
+    errors: Vec<LexError>,
+    /// The dialect and options the scanner follows.
+    config: LexerConfig,
+    /// Source text that hasn't been given to a token or trivia yet. Only kept when preserving trivia.
+    text: String,
+    /// Location where ´text´ starts.
+    text_start: Position,
+    /// Trivia waiting for the next token.
+    trivia: Vec<Trivia>,
 }
 
 impl Scanner {
-    /// Creates a new Scanner.
+    /// Creates a new Scanner for standard Mini-PL.
     pub fn new() -> Self {
+        Scanner::with_config(LexerConfig::default())
+    }
+
+    /// Creates a new Scanner that keeps whitespace and comments as trivia attached to the tokens.
+    /// The original source can be rebuilt from the tokens with ´reconstruct_source´.
+    pub fn with_trivia() -> Self {
+        Scanner::with_config(LexerConfig {
+            preserve_trivia: true,
+            ..LexerConfig::default()
+        })
+    }
+
+    /// Creates a new Scanner that follows the given configuration.
+    pub fn with_config(config: LexerConfig) -> Self {
         Scanner {
             tokens: VecDeque::new(),
             scan_mode: ScanMode::Normal,
             buffer: String::new(),
             escape_buffer: String::new(),
             block_comment_counter: 0,
+            comment: String::new(),
+            raw_hashes: 0,
+            closing_hashes: 0,
+            position: Position::start(),
+            char_len: 0,
+            token_start: Position::start(),
+            escape_start: Position::start(),
+            errors: Vec::new(),
+            config,
+            text: String::new(),
+            text_start: Position::start(),
+            trivia: Vec::new(),
         }
     }
+
+    /// Lexical errors found during scanning. Scanning continues after an error so this contains
+    /// every problem found so far.
+    pub fn errors(&self) -> &[LexError] {
+        &self.errors
+    }
+
     /// Goes trough the whole source string character by character and produces a vector of tokens.
     pub fn scan<S>(&mut self, source: &str, token_stream: &mut S)
     where
-        S: Sink<Token>,
+        S: Sink<SpannedToken>,
+    {
+        self.scan_chars(source.chars(), token_stream);
+    }
+
+    /// Reads UTF-8 encoded source text from ´reader´ until it runs out and scans it as it comes in.
+    /// Tokens are put into the sink as soon as they are complete so the rest of the pipeline can
+    /// start working before the whole input has been read.
+    /// Invalid UTF-8 is reported as an error. In string literals it's read as the replacement
+    /// character U+FFFD.
+    pub fn scan_reader<R, S>(&mut self, mut reader: R, token_stream: &mut S) -> io::Result<()>
+    where
+        R: Read,
+        S: Sink<SpannedToken>,
+    {
+        let mut bytes = vec![0; 8 * 1024];
+        // Number of bytes at the start of ´bytes´ that belong to a character that was cut in half
+        // by the previous read.
+        let mut pending = 0;
+        loop {
+            let read = match reader.read(&mut bytes[pending..]) {
+                Ok(read) => read,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            let len = pending + read;
+            let mut decoded = 0;
+            while decoded < len {
+                match str::from_utf8(&bytes[decoded..len]) {
+                    Ok(text) => {
+                        self.scan(text, token_stream);
+                        decoded = len;
+                    }
+                    Err(e) => {
+                        let valid = e.valid_up_to();
+                        let text = str::from_utf8(&bytes[decoded..decoded + valid]).unwrap();
+                        self.scan(text, token_stream);
+                        decoded += valid;
+                        match e.error_len() {
+                            Some(invalid) => {
+                                self.invalid_utf8(invalid);
+                                decoded += invalid;
+                            }
+                            // The rest might be a valid character once the next read completes it.
+                            None if read > 0 => break,
+                            None => {
+                                self.invalid_utf8(len - decoded);
+                                decoded = len;
+                            }
+                        }
+                    }
+                }
+            }
+            if read == 0 {
+                return Ok(());
+            }
+            bytes.copy_within(decoded..len, 0);
+            pending = len - decoded;
+        }
+    }
+
+    /// Reports the ´len´ bytes at the current position as invalid UTF-8 and moves past them.
+    /// Inside a string literal they stand for one replacement character, elsewhere they're skipped.
+    fn invalid_utf8(&mut self, len: usize) {
+        let span = self.position.span_to(self.position.offset + len);
+        self.errors.push(LexError {
+            kind: LexErrorKind::InvalidUtf8,
+            span,
+        });
+        match self.scan_mode {
+            ScanMode::StringLiteral | ScanMode::RawString => self.buffer.push('\u{FFFD}'),
+            _ => {}
+        }
+        if self.config.preserve_trivia {
+            // The text has to stay as long in bytes as the source for the spans to point into it.
+            self.text.extend((0..len).map(|_| '?'));
+        }
+        self.position.offset += len;
+        self.position.column += 1;
+    }
+
+    /// Scans the characters of an iterator one by one. The characters can come from any source,
+    /// for example from a reader that is still being read.
+    pub fn scan_chars<I, S>(&mut self, source: I, token_stream: &mut S)
+    where
+        I: IntoIterator<Item = char>,
+        S: Sink<SpannedToken>,
     {
+        for c in source {
+            self.scan_char(c);
+            self.flush(token_stream);
+        }
+    }
+
+    fn scan_char(&mut self, c: char) {
         use self::ScanMode::*;
-        // Foreach through the source string and choose the approriate handling function for the current character
+        self.char_len = c.len_utf8();
+        if self.config.preserve_trivia {
+            self.text.push(c);
+        }
+        // Choose the approriate handling function for the current character
         // according to what state(´ScanMode´) the scanner is currently in.
-        for c in source.chars() {
-            match self.scan_mode {
-                Normal => self.normal_scan(c),
-                StringLiteral => self.string_scan(c),
-                Number => self.number_scan(c),
-                PossibleAssignment => self.check_for_assignment(c),
-                PossibleComment => self.check_for_comment(c),
-                LineComment => self.line_comment_handling(c),
-                BlockComment => self.block_comment_handling(c),
-                Other => self.identifier_and_keyword_scan(c),
-                Escape => self.escape_scan(c),
-                Range => self.range_scan(c),
+        match self.scan_mode {
+            Normal => self.normal_scan(c),
+            StringLiteral => self.string_scan(c),
+            Number => self.number_scan(c),
+            PossibleAssignment => self.check_for_assignment(c),
+            PossibleComparison => self.comparison_scan(c),
+            PossibleComment => self.check_for_comment(c),
+            LineComment => self.line_comment_handling(c),
+            BlockComment => self.block_comment_handling(c),
+            Other => self.identifier_and_keyword_scan(c),
+            Escape => self.escape_scan(c),
+            Range => self.range_scan(c),
+            RawStringStart => self.raw_string_start_scan(c),
+            RawString => self.raw_string_scan(c),
+            RawStringEnd => self.raw_string_end_scan(c),
+        }
+        self.position.advance(c);
+    }
+
+    /// Finishes scanning at the end of the input. Tokens still waiting for more characters are
+    /// emitted and unfinished strings, escapes and comments are reported as errors.
+    /// After this the scanner is back in its initial mode and can be used for a new input.
+    pub fn finish<S>(&mut self, token_stream: &mut S)
+    where
+        S: Sink<SpannedToken>,
+    {
+        use self::ScanMode::*;
+        // There's no character left so the current position is the end of the input.
+        self.char_len = 0;
+        let start = self.token_start;
+        match self.scan_mode {
+            Normal => {}
+            LineComment => {
+                let end = self.position.offset;
+                self.end_line_comment(end);
+            }
+            StringLiteral => self.error(LexErrorKind::UnterminatedString, start),
+            Escape => {
+                let escape_start = self.escape_start;
+                self.error(LexErrorKind::DanglingEscape, escape_start);
+                self.error(LexErrorKind::UnterminatedString, start);
             }
-            while !self.tokens.is_empty() {
-                token_stream.put(self.tokens.pop_back().unwrap());
+            Number => self.eval_number_from_buffer(),
+            PossibleAssignment => self.push_token(Token::Colon),
+            PossibleComparison => self.eval_comparison_from_buffer(),
+            PossibleComment => self.push_token(Token::Operator(Operator::Divide)),
+            BlockComment => {
+                let depth = self.block_comment_counter;
+                self.error(LexErrorKind::UnterminatedBlockComment(depth), start);
+                self.push_trivia(TriviaKind::BlockComment);
             }
+            Other => self.eval_keyword_or_identifier_from_buffer(),
+            Range => self.error(LexErrorKind::IncompleteRange, start),
+            RawStringStart => self.error(LexErrorKind::InvalidRawStringStart, start),
+            RawString | RawStringEnd => self.error(LexErrorKind::UnterminatedString, start),
+        }
+        self.scan_mode = ScanMode::Normal;
+        self.buffer.clear();
+        self.escape_buffer.clear();
+        self.block_comment_counter = 0;
+        self.comment.clear();
+        self.raw_hashes = 0;
+        self.closing_hashes = 0;
+        if self.config.preserve_trivia {
+            self.token_start = self.position;
+            self.push_token(Token::EndOfInput);
+        }
+        self.flush(token_stream);
+    }
+
+    fn flush<S>(&mut self, token_stream: &mut S)
+    where
+        S: Sink<SpannedToken>,
+    {
+        while let Some(token) = self.tokens.pop_back() {
+            token_stream.put(token);
         }
     }
 
+    /// Pushes a token that ends with the character currently being scanned.
+    fn push_token(&mut self, token: Token) {
+        let end = self.position.offset + self.char_len;
+        self.push_token_ending_at(token, end);
+    }
+
+    /// Pushes a token that ended right before the character currently being scanned.
+    fn push_token_before(&mut self, token: Token) {
+        let end = self.position.offset;
+        self.push_token_ending_at(token, end);
+    }
+
+    fn push_token_ending_at(&mut self, token: Token, end: usize) {
+        let span = self.token_start.span_to(end);
+        let (leading_trivia, text) = if self.config.preserve_trivia {
+            let text = self.take_text(end);
+            (mem::take(&mut self.trivia), text)
+        } else {
+            (Vec::new(), String::new())
+        };
+        self.tokens.push_front(SpannedToken {
+            token,
+            span,
+            leading_trivia,
+            text,
+        });
+    }
+
+    /// Records trivia that started at ´token_start´ and ends with the character currently being scanned.
+    fn push_trivia(&mut self, kind: TriviaKind) {
+        let end = self.position.offset + self.char_len;
+        self.push_trivia_ending_at(kind, end);
+    }
+
+    fn push_trivia_ending_at(&mut self, kind: TriviaKind, end: usize) {
+        if !self.config.preserve_trivia {
+            return;
+        }
+        let span = self.token_start.span_to(end);
+        let text = self.take_text(end);
+        // Consecutive whitespace is gathered into one piece of trivia.
+        if let Some(last) = self.trivia.last_mut() {
+            if kind == TriviaKind::Whitespace && last.kind == kind && last.span.end == span.start {
+                last.span.end = span.end;
+                last.text.push_str(&text);
+                return;
+            }
+        }
+        self.trivia.push(Trivia { kind, span, text });
+    }
+
+    /// Takes the source text of the token or trivia that started at ´token_start´ and ends at ´end´.
+    /// Text before ´token_start´ that didn't become a part of anything is recorded as skipped trivia.
+    fn take_text(&mut self, end: usize) -> String {
+        let start = self.token_start.offset;
+        if start > self.text_start.offset {
+            let skipped: String = self.text.drain(..start - self.text_start.offset).collect();
+            self.trivia.push(Trivia {
+                kind: TriviaKind::Skipped,
+                span: self.text_start.span_to(start),
+                text: skipped,
+            });
+        }
+        self.text_start = self.token_start;
+        let text: String = self.text.drain(..end - start).collect();
+        for c in text.chars() {
+            self.text_start.advance(c);
+        }
+        text
+    }
+
+    /// Records an error that starts from ´start´ and ends with the character currently being scanned.
+    fn error(&mut self, kind: LexErrorKind, start: Position) {
+        let span = start.span_to(self.position.offset + self.char_len);
+        self.errors.push(LexError { kind, span });
+    }
+
+    /// Records an error that starts from ´start´ and ended right before the character currently being scanned.
+    fn error_before(&mut self, kind: LexErrorKind, start: Position) {
+        let span = start.span_to(self.position.offset);
+        self.errors.push(LexError { kind, span });
+    }
+
     fn normal_scan(&mut self, c: char) {
+        // Every token starts in normal mode so this is where the token's location is recorded.
+        self.token_start = self.position;
         self.scan_mode = match c {
             // These characters correspond to specific tokens and don't require any further processing.
-            '(' | ')' | ';' | '+' | '-' | '*' | '<' | '=' | '&' | '!' => {
-                self.tokens.push_front(match c {
+            '(' | ')' | '[' | ']' | ';' | ',' | '+' | '-' | '*' | '=' | '&' | '|' => {
+                self.push_token(match c {
                     '(' => Token::Bracket(Left),
                     ')' => Token::Bracket(Right),
+                    '[' => Token::SquareBracket(Left),
+                    ']' => Token::SquareBracket(Right),
                     ';' => Token::Semicolon,
+                    ',' => Token::Comma,
                     '+' => Token::Operator(Operator::Plus),
                     '-' => Token::Operator(Operator::Minus),
                     '*' => Token::Operator(Operator::Multiply),
-                    '<' => Token::Operator(Operator::LessThan),
                     '=' => Token::Operator(Operator::Equals),
                     '&' => Token::Operator(Operator::And),
-                    '!' => Token::Operator(Operator::Not),
+                    '|' => Token::Operator(Operator::Or),
                     _ => unreachable!(),
                 });
                 ScanMode::Normal
             }
-            //whitespace is just ignored.
-            ' ' | '\n' | '\t' | '\r' => ScanMode::Normal,
+            //whitespace is just ignored unless it is kept as trivia.
+            ' ' | '\n' | '\t' | '\r' => {
+                self.push_trivia(TriviaKind::Whitespace);
+                ScanMode::Normal
+            }
 
             // These characters don't correspond directly to a token so we return the appropriate scanmode.
             ':' => ScanMode::PossibleAssignment,
+            '<' | '>' | '!' => {
+                self.buffer.push(c);
+                ScanMode::PossibleComparison
+            }
             '"' => ScanMode::StringLiteral,
             '/' => ScanMode::PossibleComment,
             '.' => ScanMode::Range,
 
             // With thse we also need to save the character in the buffer as it is needed later.
-            _ => {
+            '0'...'9' => {
                 self.buffer.push(c);
-                match c {
-                    '0'...'9' => ScanMode::Number,
-                    _ => ScanMode::Other,
-                }
+                ScanMode::Number
+            }
+            c if (self.config.identifier_start)(c) => {
+                self.buffer.push(c);
+                ScanMode::Other
+            }
+            // Anything else can't start a token. The character is skipped.
+            _ => {
+                let start = self.position;
+                self.error(LexErrorKind::IllegalCharacter(c), start);
+                ScanMode::Normal
             }
         };
     }
@@ -179,26 +648,52 @@ impl Scanner {
             // Escapes need to be handled in their own mode since they are transformed into
             // their corresponding character and then inserted into the string we're reading.
             '\\' => {
+                self.escape_start = self.position;
                 self.scan_mode = ScanMode::Escape;
             }
 
             // The string literal has ended. We create a token out of the string we've built
             // into our buffer and then return to normal scanning mode.
             '"' => {
-                self.tokens
-                    .push_front(Token::StringLiteral(self.buffer.clone()));
+                let literal = Token::StringLiteral(Symbol::intern(&self.buffer));
+                self.push_token(literal);
                 self.buffer.clear();
                 self.scan_mode = ScanMode::Normal;
             }
-            //TODO: It is possible that I need to add problematic characters here. 
-            //      For example, currently multiline string literals are allowed.
+            '\n' if !self.config.multiline_strings => self.newline_in_string(),
 
             //The character does not have a special meaning and is just added to the string we're building.
             _ => self.buffer.push(c),
         }
     }
 
+    /// Ends a string literal at a line break when multi-line strings are not allowed.
+    /// The literal is kept as it is so that the rest of the line doesn't cause more errors.
+    fn newline_in_string(&mut self) {
+        let start = self.token_start;
+        self.error_before(LexErrorKind::NewlineInString, start);
+        let literal = Token::StringLiteral(Symbol::intern(&self.buffer));
+        self.push_token_before(literal);
+        self.buffer.clear();
+        self.scan_mode = ScanMode::Normal;
+        self.normal_scan('\n');
+    }
+
+    /// Returns to scanning the string literal after an escape sequence has been handled.
+    fn end_escape(&mut self) {
+        self.escape_buffer.clear();
+        self.scan_mode = ScanMode::StringLiteral;
+    }
+
     fn escape_scan(&mut self, c: char) {
+        let start = self.escape_start;
+        if c == '\n' && !self.config.multiline_strings {
+            // The escape is dropped and the string literal ends at the line break.
+            self.error_before(LexErrorKind::DanglingEscape, start);
+            self.end_escape();
+            self.string_scan(c);
+            return;
+        }
         if self.escape_buffer.is_empty() {
             //match the escape to the actual character and store it in a variable.
             let escaped_char = match c {
@@ -210,37 +705,39 @@ impl Scanner {
                 't' => '\t',
                 'v' => '\x0B',
                 '\\' | '\'' | '"' | '?' => c,
-                '0'...'8' | 'x' | 'U' | 'u' => {
+                '0'...'7' | 'x' | 'U' | 'u' => {
                     self.escape_buffer.push(c);
                     //return because in the case these characters we want to gather more characters in order to parse the escape correctly.
                     return;
                 }
-                _ => panic!("Escape \\{:#?} not supported", c),
+                _ => {
+                    // The escape is dropped and scanning continues from the next character of the string.
+                    self.error(LexErrorKind::UnsupportedEscape(c), start);
+                    self.end_escape();
+                    return;
+                }
             };
             //the escape has been handled. push the character into the string we're forming and return back to normal string scanning.
             self.buffer.push(escaped_char);
-            self.escape_buffer.clear();
-            self.scan_mode = ScanMode::StringLiteral;
+            self.end_escape();
         } else {
             //we have found an escape sequence that's larger than one character long.
             match self.escape_buffer.chars().next().unwrap() {
-                //hexadecimal escape handling
+                //hexadecimal escape handling. The escape takes at most two hex digits like in C and
+                //Rust, wider code points are written with \u{...}.
                 'x' => match c {
                     '0'...'9' | 'a'...'f' | 'A'...'F' => {
-                        //
-                        if self.escape_buffer.len() > 2 {
-                            panic!("hex escape sequences longer than a byte(two digits) are not supported.");
-                        }
                         self.escape_buffer.push(c);
+                        if self.escape_buffer.len() == 3 {
+                            let digits = self.escape_buffer[1..].to_string();
+                            self.push_code_point(&digits, LexErrorKind::MissingHexDigits, true);
+                            self.end_escape();
+                        }
                     }
                     _ => {
-                        if self.escape_buffer.len() < 2 {
-                            panic!("Atleast one hexadecimal digit is needed after \\x");
-                        }
-                        let chr = u8::from_str_radix(&self.escape_buffer[1..], 16)
-                            .expect("error occured when parsing the hex escape into a char");
-                        self.buffer.push(chr as char);
-                        self.escape_buffer.clear();
+                        let digits = self.escape_buffer[1..].to_string();
+                        self.push_code_point(&digits, LexErrorKind::MissingHexDigits, false);
+                        self.end_escape();
                         self.string_scan(c);
                     }
                 },
@@ -254,16 +751,47 @@ impl Scanner {
                         }
                     }
                     if self.escape_buffer.len() == 3 || stop {
-                        let chr = u8::from_str_radix(&self.escape_buffer[..], 8)
-                            .expect("error occured when parsing the octal escape into a char");
-                        self.buffer.push(chr as char);
-                        self.escape_buffer.clear();
-                        self.scan_mode = ScanMode::StringLiteral;
+                        match u8::from_str_radix(&self.escape_buffer[..], 8) {
+                            Ok(chr) => self.buffer.push(chr as char),
+                            Err(_) => {
+                                let kind = LexErrorKind::OctalEscapeOutOfRange;
+                                if stop {
+                                    self.error_before(kind, start);
+                                } else {
+                                    self.error(kind, start);
+                                }
+                            }
+                        }
+                        self.end_escape();
                         if stop {
                             self.string_scan(c)
                         };
                     }
                 }
+                // \u{...} takes the code point in hex between the braces.
+                'u' if self.escape_buffer.starts_with("u{") => match c {
+                    '}' => {
+                        let digits = self.escape_buffer[2..].to_string();
+                        // Invalid digits have already been reported.
+                        if digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                            self.push_code_point(&digits, LexErrorKind::EmptyUnicodeEscape, true);
+                        }
+                        self.end_escape();
+                    }
+                    '"' | '\n' => {
+                        self.error_before(LexErrorKind::UnclosedUnicodeEscape, start);
+                        self.end_escape();
+                        self.string_scan(c);
+                    }
+                    _ => {
+                        if !c.is_ascii_hexdigit() {
+                            let position = self.position;
+                            self.error(LexErrorKind::InvalidUnicodeDigit(c), position);
+                        }
+                        self.escape_buffer.push(c);
+                    }
+                },
+                'u' if c == '{' && self.escape_buffer.len() == 1 => self.escape_buffer.push(c),
                 // Unicode escapes.
                 // \U is a 4 byte unicode escape sequence and is represented as an 8 digit hexadecimal number.
                 // \u is a 2 byte unicode escape sequence and is represented as an 4 digit hexadecimal number.
@@ -271,25 +799,19 @@ impl Scanner {
                     match c {
                         '0'...'9' | 'a'...'f' | 'A'...'F' => self.escape_buffer.push(c),
                         _ => {
-                            if u == 'U' {
-                                panic!("{} is not a valid hex digit. \\U requires eight hex digits after it.",c);
-                            } else {
-                                panic!("{} is not a valid hex digit. \\u requires four hex digits after it.", c);
-                            }
+                            // The unfinished escape is dropped and the character is scanned as a
+                            // part of the string.
+                            self.error_before(LexErrorKind::InvalidUnicodeDigit(c), start);
+                            self.end_escape();
+                            self.string_scan(c);
+                            return;
                         }
                     }
                     let max_buffer_len = if u == 'U' { 8 } else { 4 } + 1;
                     if self.escape_buffer.len() == max_buffer_len {
-                        let error_string = format!(
-                            "error occured when parsing unicode escape sequence {}",
-                            self.escape_buffer
-                        );
-                        let unicode_error =
-                            format!("{} is an invalid unicode codepoint.", self.escape_buffer);
-                        let chr = from_u32(
-                            u32::from_str_radix(&self.escape_buffer[1..], 16).expect(&error_string),
-                        ).expect(&unicode_error);
-                        self.buffer.push(chr as char)
+                        let digits = self.escape_buffer[1..].to_string();
+                        self.push_code_point(&digits, LexErrorKind::MissingHexDigits, true);
+                        self.end_escape();
                     }
                 }
 
@@ -298,57 +820,177 @@ impl Scanner {
         }
     }
 
+    /// Adds the character named by the hex ´digits´ of an escape to the string literal.
+    /// ´ended´ tells if the escape ends with the character currently being scanned
+    /// or if it ended right before it.
+    fn push_code_point(&mut self, digits: &str, missing: LexErrorKind, ended: bool) {
+        let error = if digits.is_empty() {
+            Some(missing)
+        } else if digits.trim_start_matches('0').len() > 8 {
+            Some(LexErrorKind::HexEscapeTooLong)
+        } else {
+            let code = u32::from_str_radix(digits.trim_start_matches('0'), 16).unwrap_or(0);
+            match from_u32(code) {
+                Some(chr) => {
+                    self.buffer.push(chr);
+                    None
+                }
+                None => Some(LexErrorKind::InvalidCodePoint(code)),
+            }
+        };
+        let start = self.escape_start;
+        match error {
+            Some(kind) if ended => self.error(kind, start),
+            Some(kind) => self.error_before(kind, start),
+            None => {}
+        }
+    }
+
+    /// Reads the '#'s between the r and the opening quote of a raw string literal.
+    fn raw_string_start_scan(&mut self, c: char) {
+        match c {
+            '#' => self.raw_hashes += 1,
+            '"' => self.scan_mode = ScanMode::RawString,
+            _ => {
+                let start = self.token_start;
+                self.error_before(LexErrorKind::InvalidRawStringStart, start);
+                self.raw_hashes = 0;
+                self.scan_mode = ScanMode::Normal;
+                self.normal_scan(c);
+            }
+        }
+    }
+
+    /// Raw string literals have no escapes. They end at a quote followed by as many '#'s as
+    /// there were before the opening quote, so r#"say "hi""# contains ´say "hi"´.
+    fn raw_string_scan(&mut self, c: char) {
+        match c {
+            '"' if self.raw_hashes == 0 => self.end_raw_string(),
+            '"' => {
+                self.closing_hashes = 0;
+                self.scan_mode = ScanMode::RawStringEnd;
+            }
+            '\n' if !self.config.multiline_strings => self.newline_in_string(),
+            _ => self.buffer.push(c),
+        }
+    }
+
+    fn raw_string_end_scan(&mut self, c: char) {
+        if c == '#' {
+            self.closing_hashes += 1;
+            if self.closing_hashes == self.raw_hashes {
+                self.end_raw_string();
+            }
+        } else {
+            // The quote and the '#'s were a part of the string after all.
+            self.buffer.push('"');
+            for _ in 0..self.closing_hashes {
+                self.buffer.push('#');
+            }
+            self.scan_mode = ScanMode::RawString;
+            self.raw_string_scan(c);
+        }
+    }
+
+    fn end_raw_string(&mut self) {
+        let literal = Token::StringLiteral(Symbol::intern(&self.buffer));
+        self.push_token(literal);
+        self.buffer.clear();
+        self.raw_hashes = 0;
+        self.closing_hashes = 0;
+        self.scan_mode = ScanMode::Normal;
+    }
+
     fn number_scan(&mut self, c: char) {
         match c {
-            '0'...'9' => self.buffer.push(c),
+            // Letters are taken into the literal too so that radix prefixes and hex digits are
+            // recognized. Whether they are valid is checked once the whole literal has been read.
+            c if c.is_alphanumeric() || c == '_' => self.buffer.push(c),
             _ => {
-                self.tokens.push_front(
-                    self.buffer
-                        .parse()
-                        .map(Token::Number)
-                        .unwrap_or_else(|e| panic!("Number parsing failed: {}", e)),
-                );
-                self.buffer.clear();
+                self.eval_number_from_buffer();
                 self.scan_mode = ScanMode::Normal;
                 self.normal_scan(c);
             }
         }
     }
 
+    fn eval_number_from_buffer(&mut self) {
+        match parse_number(&self.buffer) {
+            Ok(number) => self.push_token_before(Token::Number(number)),
+            Err(kind) => {
+                let start = self.token_start;
+                self.error_before(kind, start);
+            }
+        }
+        self.buffer.clear();
+    }
+
     fn check_for_assignment(&mut self, c: char) {
         match c {
             '=' => {
-                self.tokens.push_front(Token::Assignment);
+                self.push_token(Token::Assignment);
+                self.scan_mode = ScanMode::Normal;
+            }
+            _ => {
+                self.push_token_before(Token::Colon);
                 self.scan_mode = ScanMode::Normal;
+                self.normal_scan(c);
             }
+        };
+    }
+
+    /// Scans the longest operator that starts with the character in the buffer.
+    fn comparison_scan(&mut self, c: char) {
+        let operator = match (&*self.buffer, c) {
+            ("<", '=') => Operator::LessThanOrEqual,
+            ("<", '>') | ("!", '=') => Operator::NotEquals,
+            (">", '=') => Operator::GreaterThanOrEqual,
             _ => {
-                self.tokens.push_front(Token::Colon);
+                self.eval_comparison_from_buffer();
                 self.scan_mode = ScanMode::Normal;
                 self.normal_scan(c);
+                return;
             }
         };
+        self.buffer.clear();
+        self.push_token(Token::Operator(operator));
+        self.scan_mode = ScanMode::Normal;
+    }
+
+    /// Turns the character in the buffer into a single character operator.
+    fn eval_comparison_from_buffer(&mut self) {
+        let operator = match &*self.buffer {
+            "<" => Operator::LessThan,
+            ">" => Operator::GreaterThan,
+            "!" => Operator::Not,
+            _ => unreachable!("the buffer did not contain the start of a comparison"),
+        };
+        self.buffer.clear();
+        self.push_token_before(Token::Operator(operator));
     }
 
     fn eval_keyword_or_identifier_from_buffer(&mut self) {
-        self.tokens.push_front(match &*self.buffer {
-            "var" => Token::KeyWord(KeyWord::Var),
-            "end" => Token::KeyWord(KeyWord::End),
-            "for" => Token::KeyWord(KeyWord::For),
-            "in" => Token::KeyWord(KeyWord::In),
-            "do" => Token::KeyWord(KeyWord::Do),
-            "read" => Token::KeyWord(KeyWord::Read),
-            "print" => Token::KeyWord(KeyWord::Print),
-            "int" => Token::KeyWord(KeyWord::Int),
-            "string" => Token::KeyWord(KeyWord::String),
-            "bool" => Token::KeyWord(KeyWord::Bool),
-            "assert" => Token::KeyWord(KeyWord::Assert),
-            _ => Token::Identifier(self.buffer.clone()),
-        });
+        if self.config.normalize_identifiers {
+            self.buffer = self.buffer.nfc().collect();
+        }
+        let token = match self.config.keyword(&self.buffer) {
+            Some(keyword) => Token::KeyWord(keyword),
+            None => Token::Identifier(Symbol::intern(&self.buffer)),
+        };
+        self.push_token_before(token);
         self.buffer.clear();
     }
 
     fn identifier_and_keyword_scan(&mut self, c: char) {
-        if c.is_alphanumeric() || c == '_' {
+        if self.config.raw_strings && self.buffer == "r" && (c == '"' || c == '#') {
+            self.buffer.clear();
+            self.scan_mode = if c == '"' {
+                ScanMode::RawString
+            } else {
+                self.raw_hashes = 1;
+                ScanMode::RawStringStart
+            };
+        } else if (self.config.identifier_continue)(c) {
             self.buffer.push(c);
         } else {
             self.eval_keyword_or_identifier_from_buffer();
@@ -359,13 +1001,16 @@ impl Scanner {
 
     fn range_scan(&mut self, c: char) {
         match c {
-            '.' => self.buffer.push(c),
-            _ => panic!("Tried to scan for a range but failed."),
-        }
-        if !self.buffer.is_empty() {
-            self.buffer.clear();
-            self.tokens.push_front(Token::Range);
-            self.scan_mode = ScanMode::Normal;
+            '.' => {
+                self.push_token(Token::Range);
+                self.scan_mode = ScanMode::Normal;
+            }
+            _ => {
+                let start = self.token_start;
+                self.error_before(LexErrorKind::IncompleteRange, start);
+                self.scan_mode = ScanMode::Normal;
+                self.normal_scan(c);
+            }
         }
     }
 
@@ -377,37 +1022,58 @@ impl Scanner {
                 self.scan_mode = ScanMode::BlockComment;
             }
             _ => {
-                self.tokens.push_front(Token::Operator(Operator::Divide));
+                self.push_token_before(Token::Operator(Operator::Divide));
                 self.scan_mode = ScanMode::Normal;
+                self.normal_scan(c);
             }
         }
     }
 
     fn block_comment_handling(&mut self, c: char) {
-        if c == '*' || c == '/' {
-            let b = self.buffer.pop();
-            if b != Some(c) {
-                self.buffer.extend(b);
-                self.buffer.push(c);
-            }
-            if self.buffer == "/*" {
-                self.block_comment_counter += 1;
-                self.buffer.clear();
-            } else if self.buffer == "*/" {
+        self.comment.push(c);
+        // The buffer holds the previous character if it could start a "/*" or end a "*/".
+        match (self.buffer.pop(), c) {
+            (Some('/'), '*') => self.block_comment_counter += 1,
+            (Some('*'), '/') => {
                 self.block_comment_counter -= 1;
-                self.buffer.clear();
                 if self.block_comment_counter == 0 {
+                    self.end_block_comment();
                     self.scan_mode = ScanMode::Normal;
                 }
             }
+            (_, '*') | (_, '/') => self.buffer.push(c),
+            _ => {}
+        }
+    }
+
+    fn end_block_comment(&mut self) {
+        let comment = mem::take(&mut self.comment);
+        let text = &comment[..comment.len() - "*/".len()];
+        if text.starts_with('*') && !text.starts_with("**") && text != "*" {
+            self.push_token(Token::DocComment(Symbol::intern(&text[1..])));
         } else {
-            self.buffer.clear();
+            self.push_trivia(TriviaKind::BlockComment);
         }
     }
 
     fn line_comment_handling(&mut self, c: char) {
         if c == '\n' {
+            let end = self.position.offset;
+            self.end_line_comment(end);
             self.scan_mode = ScanMode::Normal;
+            self.normal_scan(c);
+        } else {
+            self.comment.push(c);
+        }
+    }
+
+    /// Ends the line comment at ´end´, which is where the line or the input ends.
+    fn end_line_comment(&mut self, end: usize) {
+        let comment = mem::take(&mut self.comment);
+        if comment.starts_with('/') && !comment.starts_with("//") {
+            self.push_token_ending_at(Token::DocComment(Symbol::intern(&comment[1..])), end);
+        } else {
+            self.push_trivia_ending_at(TriviaKind::LineComment, end);
         }
     }
 }
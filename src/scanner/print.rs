@@ -0,0 +1,134 @@
+use std::fmt;
+
+use util::Direction;
+
+use super::{KeyWord, Operator, Token};
+
+/// Turns tokens back into source text. Scanning the text gives back the same tokens as long as
+/// the tokens are ones the scanner can produce: identifiers can't be keywords and numbers can't
+/// be negative.
+pub fn print_tokens(tokens: &[Token]) -> String {
+    let mut source = String::new();
+    let mut previous: Option<&Token> = None;
+    for token in tokens {
+        if *token == Token::EndOfInput {
+            continue;
+        }
+        match (previous, token) {
+            (None, _)
+            | (Some(&Token::Bracket(Direction::Left)), _)
+            | (Some(&Token::SquareBracket(Direction::Left)), _) => {}
+            (Some(&Token::Semicolon), _) | (Some(&Token::DocComment(_)), _) => source.push('\n'),
+            (_, &Token::Semicolon)
+            | (_, &Token::Comma)
+            | (_, &Token::Bracket(Direction::Right))
+            | (_, &Token::SquareBracket(Direction::Right)) => {}
+            // Everything else is separated by a space so that neighbouring tokens don't merge
+            // into one, e.g. ´<´ and ´=´ into ´<=´ or ´/´ and ´*´ into a comment.
+            _ => source.push(' '),
+        }
+        source.push_str(&token.to_string());
+        previous = Some(token);
+    }
+    source
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Token::Bracket(Direction::Left) => write!(f, "("),
+            Token::Bracket(Direction::Right) => write!(f, ")"),
+            Token::SquareBracket(Direction::Left) => write!(f, "["),
+            Token::SquareBracket(Direction::Right) => write!(f, "]"),
+            Token::Identifier(identifier) => write!(f, "{}", identifier),
+            Token::StringLiteral(literal) => {
+                write!(f, "\"")?;
+                for c in literal.as_str().chars() {
+                    write_escaped(f, c)?;
+                }
+                write!(f, "\"")
+            }
+            Token::Number(ref number) => write!(f, "{}", number),
+            Token::Semicolon => write!(f, ";"),
+            Token::Colon => write!(f, ":"),
+            Token::Comma => write!(f, ","),
+            Token::Assignment => write!(f, ":="),
+            Token::Operator(ref operator) => write!(f, "{}", operator),
+            Token::KeyWord(ref keyword) => write!(f, "{}", keyword),
+            Token::Range => write!(f, ".."),
+            // Line comments are used when possible. They need to be followed by a line break.
+            Token::DocComment(text) if text.as_str().contains('\n') => write!(f, "/**{}*/", text),
+            Token::DocComment(text) => write!(f, "///{}", text),
+            Token::EndOfInput => Ok(()),
+        }
+    }
+}
+
+/// Writes a character of a string literal, escaping it if the scanner would not read it back as is.
+fn write_escaped(f: &mut fmt::Formatter, c: char) -> fmt::Result {
+    match c {
+        '"' => write!(f, "\\\""),
+        '\\' => write!(f, "\\\\"),
+        '\n' => write!(f, "\\n"),
+        '\r' => write!(f, "\\r"),
+        '\t' => write!(f, "\\t"),
+        '\x07' => write!(f, "\\a"),
+        '\x08' => write!(f, "\\b"),
+        '\x0B' => write!(f, "\\v"),
+        '\x0C' => write!(f, "\\f"),
+        // Octal escapes always have three digits so the escape can't take in a digit after it.
+        c if c.is_control() && (c as u32) <= 0o377 => write!(f, "\\{:03o}", c as u32),
+        c => write!(f, "{}", c),
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operator = match *self {
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+            Operator::LessThan => "<",
+            Operator::GreaterThan => ">",
+            Operator::LessThanOrEqual => "<=",
+            Operator::GreaterThanOrEqual => ">=",
+            Operator::Equals => "=",
+            Operator::NotEquals => "!=",
+            Operator::And => "&",
+            Operator::Or => "|",
+            Operator::Not => "!",
+        };
+        f.write_str(operator)
+    }
+}
+
+impl fmt::Display for KeyWord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let keyword = match *self {
+            KeyWord::Var => "var",
+            KeyWord::For => "for",
+            KeyWord::End => "end",
+            KeyWord::In => "in",
+            KeyWord::Do => "do",
+            KeyWord::Read => "read",
+            KeyWord::Print => "print",
+            KeyWord::Int => "int",
+            KeyWord::String => "string",
+            KeyWord::Bool => "bool",
+            KeyWord::Assert => "assert",
+            KeyWord::If => "if",
+            KeyWord::Then => "then",
+            KeyWord::Else => "else",
+            KeyWord::While => "while",
+            KeyWord::True => "true",
+            KeyWord::False => "false",
+            KeyWord::Procedure => "procedure",
+            KeyWord::Function => "function",
+            KeyWord::Return => "return",
+            KeyWord::Array => "array",
+            KeyWord::Of => "of",
+        };
+        f.write_str(keyword)
+    }
+}
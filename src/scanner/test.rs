@@ -1,19 +1,68 @@
-use super::{KeyWord, Operator, Scanner, Token};
-use util::Direction;
+use super::{reconstruct_source, Dialect, EditError, IncrementalScanner, KeyWord, LexerConfig, LexError, LexErrorKind, Operator, print_tokens, Scanner, SpannedToken,
+            Token, TriviaKind};
+use std::io::{self, Read};
+use std::ops::Range;
+
+use util::{Direction, Span};
+
+fn scan(source: &str) -> Vec<Token> {
+    scan_spanned(source).into_iter().map(|t| t.token).collect()
+}
+
+fn scan_spanned(source: &str) -> Vec<SpannedToken> {
+    let mut scanner = Scanner::new();
+    let mut tokens = Vec::new();
+    scanner.scan(source, &mut tokens);
+    scanner.finish(&mut tokens);
+    tokens
+}
+
+/// Scans the whole source with the configuration and returns the tokens and the errors.
+fn scan_with(config: LexerConfig, source: &str) -> (Vec<Token>, Vec<LexError>) {
+    let mut scanner = Scanner::with_config(config);
+    let mut tokens = Vec::new();
+    scanner.scan(source, &mut tokens);
+    scanner.finish(&mut tokens);
+    let tokens = tokens.into_iter().map(|t| t.token).collect();
+    (tokens, scanner.errors().to_vec())
+}
+
+/// Like ´scan_with´ but only keeps the kinds of the errors.
+fn scan_error_kinds(config: LexerConfig, source: &str) -> (Vec<Token>, Vec<LexErrorKind>) {
+    let (tokens, errors) = scan_with(config, source);
+    (tokens, errors.into_iter().map(|e| e.kind).collect())
+}
+
+fn span(start: usize, end: usize, line: usize, column: usize) -> Span {
+    Span {
+        start,
+        end,
+        line,
+        column,
+    }
+}
+
+fn string(s: &str) -> Token {
+    Token::StringLiteral(s.into())
+}
+
+fn error(kind: LexErrorKind, start: usize, end: usize, line: usize, column: usize) -> LexError {
+    LexError {
+        kind,
+        span: span(start, end, line, column),
+    }
+}
 
 #[test]
 fn example_program_1() {
     let source = r#" var X : int := 4 + (6 * 2);
  print X;"#;
 
-    let mut scanner = Scanner::new();
-    let mut tokens = Vec::new();
-    scanner.scan(source, &mut tokens);
     assert_eq!(
-        tokens,
+        scan(source),
         vec![
             Token::KeyWord(KeyWord::Var),
-            Token::Identifier(String::from("X")),
+            Token::Identifier("X".into()),
             Token::Colon,
             Token::KeyWord(KeyWord::Int),
             Token::Assignment,
@@ -26,7 +75,7 @@ fn example_program_1() {
             Token::Bracket(Direction::Right),
             Token::Semicolon,
             Token::KeyWord(KeyWord::Print),
-            Token::Identifier(String::from("X")),
+            Token::Identifier("X".into()),
             Token::Semicolon,
         ]
     );
@@ -43,53 +92,50 @@ fn example_program_2() {
   print " : Hello, World!\n";
  end for;
  assert (x = nTimes);"#;
-    let mut scanner = Scanner::new();
-    let mut tokens = Vec::new();
-    scanner.scan(source, &mut tokens);
     assert_eq!(
-        tokens,
+        scan(source),
         vec![
             Token::KeyWord(KeyWord::Var),
-            Token::Identifier(String::from("nTimes")),
+            Token::Identifier("nTimes".into()),
             Token::Colon,
             Token::KeyWord(KeyWord::Int),
             Token::Assignment,
             Token::Number(0.into()),
             Token::Semicolon,
             Token::KeyWord(KeyWord::Print),
-            Token::StringLiteral(String::from("How many times?")),
+            Token::StringLiteral("How many times?".into()),
             Token::Semicolon,
             Token::KeyWord(KeyWord::Read),
-            Token::Identifier(String::from("nTimes")),
+            Token::Identifier("nTimes".into()),
             Token::Semicolon,
             Token::KeyWord(KeyWord::Var),
-            Token::Identifier(String::from("x")),
+            Token::Identifier("x".into()),
             Token::Colon,
             Token::KeyWord(KeyWord::Int),
             Token::Semicolon,
             Token::KeyWord(KeyWord::For),
-            Token::Identifier(String::from("x")),
+            Token::Identifier("x".into()),
             Token::KeyWord(KeyWord::In),
             Token::Number(0.into()),
             Token::Range,
-            Token::Identifier(String::from("nTimes")),
+            Token::Identifier("nTimes".into()),
             Token::Operator(Operator::Minus),
             Token::Number(1.into()),
             Token::KeyWord(KeyWord::Do),
             Token::KeyWord(KeyWord::Print),
-            Token::Identifier(String::from("x")),
+            Token::Identifier("x".into()),
             Token::Semicolon,
             Token::KeyWord(KeyWord::Print),
-            Token::StringLiteral(String::from(" : Hello, World!\n")),
+            Token::StringLiteral(" : Hello, World!\n".into()),
             Token::Semicolon,
             Token::KeyWord(KeyWord::End),
             Token::KeyWord(KeyWord::For),
             Token::Semicolon,
             Token::KeyWord(KeyWord::Assert),
             Token::Bracket(Direction::Left),
-            Token::Identifier(String::from("x")),
+            Token::Identifier("x".into()),
             Token::Operator(Operator::Equals),
-            Token::Identifier(String::from("nTimes")),
+            Token::Identifier("nTimes".into()),
             Token::Bracket(Direction::Right),
             Token::Semicolon,
         ]
@@ -108,57 +154,696 @@ fn example_program_3() {
  end for;
  print "The result is: ";
  print v; "#;
-    let mut scanner = Scanner::new();
-    let mut tokens = Vec::new();
-    scanner.scan(source, &mut tokens);
     assert_eq!(
-        tokens,
+        scan(source),
         vec![
             Token::KeyWord(KeyWord::Print),
-            Token::StringLiteral(String::from("Give a number")),
+            Token::StringLiteral("Give a number".into()),
             Token::Semicolon,
             Token::KeyWord(KeyWord::Var),
-            Token::Identifier(String::from("n")),
+            Token::Identifier("n".into()),
             Token::Colon,
             Token::KeyWord(KeyWord::Int),
             Token::Semicolon,
             Token::KeyWord(KeyWord::Read),
-            Token::Identifier(String::from("n")),
+            Token::Identifier("n".into()),
             Token::Semicolon,
             Token::KeyWord(KeyWord::Var),
-            Token::Identifier(String::from("v")),
+            Token::Identifier("v".into()),
             Token::Colon,
             Token::KeyWord(KeyWord::Int),
             Token::Assignment,
             Token::Number(1.into()),
             Token::Semicolon,
             Token::KeyWord(KeyWord::Var),
-            Token::Identifier(String::from("i")),
+            Token::Identifier("i".into()),
             Token::Colon,
             Token::KeyWord(KeyWord::Int),
             Token::Semicolon,
             Token::KeyWord(KeyWord::For),
-            Token::Identifier(String::from("i")),
+            Token::Identifier("i".into()),
             Token::KeyWord(KeyWord::In),
             Token::Number(1.into()),
             Token::Range,
-            Token::Identifier(String::from("n")),
+            Token::Identifier("n".into()),
             Token::KeyWord(KeyWord::Do),
-            Token::Identifier(String::from("v")),
+            Token::Identifier("v".into()),
             Token::Assignment,
-            Token::Identifier(String::from("v")),
+            Token::Identifier("v".into()),
             Token::Operator(Operator::Multiply),
-            Token::Identifier(String::from("i")),
+            Token::Identifier("i".into()),
             Token::Semicolon,
             Token::KeyWord(KeyWord::End),
             Token::KeyWord(KeyWord::For),
             Token::Semicolon,
             Token::KeyWord(KeyWord::Print),
-            Token::StringLiteral(String::from("The result is: ")),
+            Token::StringLiteral("The result is: ".into()),
+            Token::Semicolon,
+            Token::KeyWord(KeyWord::Print),
+            Token::Identifier("v".into()),
+            Token::Semicolon,
+        ]
+    );
+}
+
+#[test]
+fn token_spans() {
+    let source = "var s : string := \"h\\nä\";\n  s := s/2 ;";
+    let spans: Vec<Span> = scan_spanned(source).into_iter().map(|t| t.span).collect();
+    assert_eq!(
+        spans,
+        vec![
+            span(0, 3, 1, 1),
+            span(4, 5, 1, 5),
+            span(6, 7, 1, 7),
+            span(8, 14, 1, 9),
+            span(15, 17, 1, 16),
+            span(18, 25, 1, 19),
+            span(25, 26, 1, 25),
+            span(29, 30, 2, 3),
+            span(31, 33, 2, 5),
+            span(34, 35, 2, 8),
+            span(35, 36, 2, 9),
+            span(36, 37, 2, 10),
+            span(38, 39, 2, 12),
+        ]
+    );
+}
+
+#[test]
+fn lexical_errors_are_reported_and_scanning_continues() {
+    let source = "print \"a\\qb\\x\\777\";\nx := 1 . 2;\nprint \"\\x41\";";
+    let mut scanner = Scanner::new();
+    let mut tokens = Vec::new();
+    scanner.scan(source, &mut tokens);
+    let tokens: Vec<Token> = tokens.into_iter().map(|t| t.token).collect();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::KeyWord(KeyWord::Print),
+            Token::StringLiteral("ab".into()),
+            Token::Semicolon,
+            Token::Identifier("x".into()),
+            Token::Assignment,
+            Token::Number(1.into()),
+            Token::Number(2.into()),
             Token::Semicolon,
             Token::KeyWord(KeyWord::Print),
-            Token::Identifier(String::from("v")),
+            Token::StringLiteral("A".into()),
             Token::Semicolon,
         ]
     );
+    assert_eq!(
+        scanner.errors(),
+        &[
+            error(LexErrorKind::UnsupportedEscape('q'), 8, 10, 1, 9),
+            error(LexErrorKind::MissingHexDigits, 11, 13, 1, 12),
+            error(LexErrorKind::OctalEscapeOutOfRange, 13, 17, 1, 14),
+            error(LexErrorKind::IncompleteRange, 27, 28, 2, 8),
+        ][..]
+    );
+}
+
+#[test]
+fn pending_tokens_are_emitted_at_the_end_of_input() {
+    assert_eq!(
+        scan("x := y / 10"),
+        vec![
+            Token::Identifier("x".into()),
+            Token::Assignment,
+            Token::Identifier("y".into()),
+            Token::Operator(Operator::Divide),
+            Token::Number(10.into()),
+        ]
+    );
+    assert_eq!(scan("print x"), scan("print x\n"));
+}
+
+#[test]
+fn unfinished_input_is_reported() {
+    let check = |source: &str, expected: Vec<LexErrorKind>| {
+        let mut scanner = Scanner::new();
+        let mut tokens = Vec::new();
+        scanner.scan(source, &mut tokens);
+        assert!(scanner.errors().is_empty());
+        scanner.finish(&mut tokens);
+        let kinds: Vec<LexErrorKind> = scanner.errors().iter().map(|e| e.kind.clone()).collect();
+        assert_eq!(kinds, expected);
+    };
+    check("print \"abc", vec![LexErrorKind::UnterminatedString]);
+    check(
+        "print \"abc\\",
+        vec![LexErrorKind::DanglingEscape, LexErrorKind::UnterminatedString],
+    );
+    check(
+        "/* a /* b */",
+        vec![LexErrorKind::UnterminatedBlockComment(1)],
+    );
+    check(
+        "/* a /* b ",
+        vec![LexErrorKind::UnterminatedBlockComment(2)],
+    );
+    check("// a line comment", vec![]);
+}
+
+#[test]
+fn trivia_preserving_scan_is_lossless() {
+    let source = " var x : int := 1; // one\n/* a /* nested */ comment **/\tprint x/2;\n\"unterminated ";
+    let mut scanner = Scanner::with_trivia();
+    let mut tokens = Vec::new();
+    scanner.scan(source, &mut tokens);
+    scanner.finish(&mut tokens);
+    assert_eq!(reconstruct_source(&tokens), source);
+
+    let print = &tokens[7];
+    assert_eq!(print.token, Token::KeyWord(KeyWord::Print));
+    assert_eq!(print.text, "print");
+    let trivia: Vec<(TriviaKind, &str)> = print
+        .leading_trivia
+        .iter()
+        .map(|t| (t.kind, &*t.text))
+        .collect();
+    assert_eq!(
+        trivia,
+        vec![
+            (TriviaKind::Whitespace, " "),
+            (TriviaKind::LineComment, "// one"),
+            (TriviaKind::Whitespace, "\n"),
+            (TriviaKind::BlockComment, "/* a /* nested */ comment **/"),
+            (TriviaKind::Whitespace, "\t"),
+        ]
+    );
+    let end = tokens.last().unwrap();
+    assert_eq!(end.token, Token::EndOfInput);
+    assert_eq!(end.leading_trivia[1].kind, TriviaKind::Skipped);
+    assert_eq!(end.leading_trivia[1].span.line, 3);
+}
+
+/// A reader that hands out its bytes one at a time to split characters between reads.
+struct ByteByByte<'a>(&'a [u8]);
+
+impl<'a> Read for ByteByByte<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.split_first() {
+            Some((&byte, rest)) if !buf.is_empty() => {
+                buf[0] = byte;
+                self.0 = rest;
+                Ok(1)
+            }
+            _ => Ok(0),
+        }
+    }
+}
+
+#[test]
+fn scanning_from_a_reader() {
+    let source = "var äö : string := \"ÿ€😀\";\nprint äö;";
+    let mut scanner = Scanner::new();
+    let mut tokens = Vec::new();
+    scanner
+        .scan_reader(ByteByByte(source.as_bytes()), &mut tokens)
+        .unwrap();
+    scanner.finish(&mut tokens);
+    assert!(scanner.errors().is_empty());
+    assert_eq!(tokens, scan_spanned(source));
+
+    let mut scanner = Scanner::new();
+    let mut tokens = Vec::new();
+    scanner
+        .scan_reader(ByteByByte(b"print \"a\xFFb\"; print \xFE\xFF 1; print \xE2\x82"), &mut tokens)
+        .unwrap();
+    scanner.finish(&mut tokens);
+    assert_eq!(tokens[1].token, Token::StringLiteral("a\u{FFFD}b".into()));
+    // The invalid bytes take up only their own length in the spans that come after them.
+    assert_eq!((tokens[4].span.start, tokens[4].span.end, tokens[4].span.column), (22, 23, 23));
+    let errors: Vec<(LexErrorKind, usize, usize)> = scanner
+        .errors()
+        .iter()
+        .map(|e| (e.kind.clone(), e.span.start, e.span.end))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            (LexErrorKind::InvalidUtf8, 8, 9),
+            (LexErrorKind::InvalidUtf8, 19, 20),
+            (LexErrorKind::InvalidUtf8, 20, 21),
+            (LexErrorKind::InvalidUtf8, 31, 33),
+        ]
+    );
+}
+
+#[test]
+fn scanning_from_a_char_iterator() {
+    let source = "print 1..2;";
+    let mut scanner = Scanner::new();
+    let mut tokens = Vec::new();
+    scanner.scan_chars(source.chars().collect::<Vec<char>>(), &mut tokens);
+    scanner.finish(&mut tokens);
+    assert_eq!(tokens, scan_spanned(source));
+}
+
+#[test]
+fn integer_literal_prefixes_and_separators() {
+    assert_eq!(
+        scan("0x1F 0b1010 0o17 1_000_000 0xdead_BEEF 007"),
+        vec![
+            Token::Number(31.into()),
+            Token::Number(10.into()),
+            Token::Number(15.into()),
+            Token::Number(1000000.into()),
+            Token::Number(0xdeadbeefu32.into()),
+            Token::Number(7.into()),
+        ]
+    );
+
+    let (tokens, errors) = scan_with(LexerConfig::default(), "0x; 1__0; 10_; 0b102; 0o8;\n12ab");
+    assert_eq!(tokens, vec![Token::Semicolon; 5]);
+    assert_eq!(
+        errors,
+        vec![
+            error(LexErrorKind::MissingDigits, 0, 2, 1, 1),
+            error(LexErrorKind::MisplacedSeparator, 4, 8, 1, 5),
+            error(LexErrorKind::MisplacedSeparator, 10, 13, 1, 11),
+            error(LexErrorKind::InvalidDigit('2', 2), 15, 20, 1, 16),
+            error(LexErrorKind::InvalidDigit('8', 8), 22, 25, 1, 23),
+            error(LexErrorKind::InvalidDigit('a', 10), 27, 31, 2, 1),
+        ]
+    );
+}
+
+#[test]
+fn dialects_and_keyword_tables() {
+    let scan_keywords = |config: LexerConfig| {
+        let (tokens, errors) = scan_with(config, "if While TRUE false $x");
+        assert_eq!(errors, vec![error(LexErrorKind::IllegalCharacter('$'), 20, 21, 1, 21)]);
+        tokens
+    };
+    assert_eq!(
+        scan_keywords(LexerConfig::new(Dialect::Standard)),
+        vec![
+            Token::Identifier("if".into()),
+            Token::Identifier("While".into()),
+            Token::Identifier("TRUE".into()),
+            Token::Identifier("false".into()),
+            Token::Identifier("x".into()),
+        ]
+    );
+    assert_eq!(
+        scan_keywords(LexerConfig::new(Dialect::Extended)),
+        vec![
+            Token::KeyWord(KeyWord::If),
+            Token::Identifier("While".into()),
+            Token::Identifier("TRUE".into()),
+            Token::KeyWord(KeyWord::False),
+            Token::Identifier("x".into()),
+        ]
+    );
+    assert_eq!(
+        scan_keywords(LexerConfig {
+            case_sensitive_keywords: false,
+            ..LexerConfig::new(Dialect::Extended)
+        }),
+        vec![
+            Token::KeyWord(KeyWord::If),
+            Token::KeyWord(KeyWord::While),
+            Token::KeyWord(KeyWord::True),
+            Token::KeyWord(KeyWord::False),
+            Token::Identifier("x".into()),
+        ]
+    );
+
+    let mut config = LexerConfig::new(Dialect::Standard);
+    config.identifier_start = |c| c.is_alphabetic() || c == '$';
+    config.keywords.remove("print");
+    assert_eq!(
+        scan_with(config, "print $x;").0,
+        vec![
+            Token::Identifier("print".into()),
+            Token::Identifier("$x".into()),
+            Token::Semicolon,
+        ]
+    );
+}
+
+#[test]
+fn multi_character_operators() {
+    assert_eq!(
+        scan("a<b<=c<>d!=e>f>=g|h&!i=j<"),
+        vec![
+            Token::Identifier("a".into()),
+            Token::Operator(Operator::LessThan),
+            Token::Identifier("b".into()),
+            Token::Operator(Operator::LessThanOrEqual),
+            Token::Identifier("c".into()),
+            Token::Operator(Operator::NotEquals),
+            Token::Identifier("d".into()),
+            Token::Operator(Operator::NotEquals),
+            Token::Identifier("e".into()),
+            Token::Operator(Operator::GreaterThan),
+            Token::Identifier("f".into()),
+            Token::Operator(Operator::GreaterThanOrEqual),
+            Token::Identifier("g".into()),
+            Token::Operator(Operator::Or),
+            Token::Identifier("h".into()),
+            Token::Operator(Operator::And),
+            Token::Operator(Operator::Not),
+            Token::Identifier("i".into()),
+            Token::Operator(Operator::Equals),
+            Token::Identifier("j".into()),
+            Token::Operator(Operator::LessThan),
+        ]
+    );
+}
+
+#[test]
+fn incremental_scanning_matches_a_full_rescan() {
+    let source = "var x : int := 1;\nvar y : string := \"a\nb\";\nprint x + 2;\n// end\nprint y;\n";
+    let edits: Vec<(usize, usize, &str)> = vec![
+        // Rename x on the first line.
+        (4, 5, "count"),
+        // Open a block comment that swallows the rest of the input.
+        (0, 0, "/* "),
+        // And close it again on the third line.
+        (40, 40, " */"),
+        // Break a string literal so that it is left unterminated.
+        (27, 28, ""),
+        // Join two lines.
+        (17, 18, " "),
+        // Add a line to the end.
+        (62, 62, "\nassert (x = 1);"),
+    ];
+    for &preserve_trivia in &[false, true] {
+        let config = LexerConfig {
+            preserve_trivia,
+            ..LexerConfig::default()
+        };
+        let mut incremental = IncrementalScanner::new(config.clone(), source);
+        for &(start, end, text) in &edits {
+            let old_tokens = incremental.tokens().to_vec();
+            let delta = incremental.edit(start..end, text).unwrap();
+
+            let mut scanner = Scanner::with_config(config.clone());
+            let mut expected = Vec::new();
+            scanner.scan(incremental.source(), &mut expected);
+            scanner.finish(&mut expected);
+            assert_eq!(incremental.tokens(), &expected[..]);
+            assert_eq!(incremental.errors(), scanner.errors());
+
+            // Applying the delta to the old tokens gives the new ones apart from the spans.
+            let mut patched = old_tokens.clone();
+            let end = delta.start + delta.removed;
+            patched.splice(delta.start..end, delta.inserted.iter().cloned());
+            let tokens = |tokens: &[SpannedToken]| -> Vec<Token> {
+                tokens.iter().map(|t| t.token.clone()).collect()
+            };
+            assert_eq!(tokens(&patched), tokens(&expected));
+        }
+        if preserve_trivia {
+            assert_eq!(reconstruct_source(incremental.tokens()), incremental.source());
+        }
+    }
+}
+
+#[test]
+fn incremental_edits_only_relex_the_edited_lines() {
+    let source = "var x : int := 1;\nprint x;\nprint x + 1;\n";
+    let mut incremental = IncrementalScanner::new(LexerConfig::default(), source);
+    let delta = incremental.edit(24..25, "y").unwrap();
+    assert_eq!(delta.start, 7);
+    assert_eq!(delta.removed, 3);
+    assert_eq!(
+        delta.inserted.into_iter().map(|t| t.token).collect::<Vec<_>>(),
+        vec![
+            Token::KeyWord(KeyWord::Print),
+            Token::Identifier("y".into()),
+            Token::Semicolon,
+        ]
+    );
+    let last = incremental.tokens().last().unwrap();
+    assert_eq!(last.token, Token::Semicolon);
+    assert_eq!(last.span.start, source.len() - 2);
+}
+
+#[test]
+fn incremental_edits_need_a_range_of_whole_characters() {
+    let source = "print \"ä€\";\n";
+    let mut incremental = IncrementalScanner::new(LexerConfig::default(), source);
+    let tokens = incremental.tokens().to_vec();
+    // ´ä´ takes the bytes 7..9 and ´€´ the bytes 9..12.
+    assert_eq!(incremental.edit(8..9, "a"), Err(EditError::NotCharBoundary(8)));
+    assert_eq!(incremental.edit(9..10, "e"), Err(EditError::NotCharBoundary(10)));
+    let reversed = Range { start: 9, end: 7 };
+    assert_eq!(incremental.edit(reversed.clone(), ""), Err(EditError::Reversed(reversed)));
+    assert_eq!(incremental.edit(12..20, ""), Err(EditError::OutOfBounds(12..20, 15)));
+    assert_eq!(incremental.source(), source);
+    assert_eq!(incremental.tokens(), &tokens[..]);
+    incremental.edit(9..12, "e").unwrap();
+    assert_eq!(incremental.tokens()[1].token, Token::StringLiteral("äe".into()));
+}
+
+#[test]
+fn printed_tokens_scan_back_to_the_same_tokens() {
+    let tokens = vec![
+        Token::KeyWord(KeyWord::Var),
+        Token::Identifier("x".into()),
+        Token::Colon,
+        Token::KeyWord(KeyWord::String),
+        Token::Assignment,
+        Token::StringLiteral("quote \" backslash \\ tab\tnewline\n\x07\x08\x0B\x0C\r\0\x01\x7F\u{85}ä€😀".into()),
+        Token::Semicolon,
+        Token::KeyWord(KeyWord::For),
+        Token::Identifier("i".into()),
+        Token::KeyWord(KeyWord::In),
+        Token::Number(0.into()),
+        Token::Range,
+        Token::Number(10.into()),
+        Token::KeyWord(KeyWord::Do),
+        Token::KeyWord(KeyWord::Assert),
+        Token::Operator(Operator::Not),
+        Token::Bracket(Direction::Left),
+        Token::Bracket(Direction::Left),
+        Token::Number(1.into()),
+        Token::Operator(Operator::LessThan),
+        Token::Operator(Operator::Equals),
+        Token::Operator(Operator::Divide),
+        Token::Operator(Operator::Multiply),
+        Token::Operator(Operator::Divide),
+        Token::Operator(Operator::Divide),
+        Token::Operator(Operator::GreaterThanOrEqual),
+        Token::Operator(Operator::Not),
+        Token::Operator(Operator::NotEquals),
+        Token::Operator(Operator::LessThanOrEqual),
+        Token::Operator(Operator::GreaterThan),
+        Token::Operator(Operator::And),
+        Token::Operator(Operator::Or),
+        Token::Operator(Operator::Plus),
+        Token::Operator(Operator::Minus),
+        Token::Bracket(Direction::Right),
+        Token::Bracket(Direction::Right),
+        Token::Semicolon,
+        Token::KeyWord(KeyWord::End),
+        Token::KeyWord(KeyWord::For),
+        Token::Colon,
+        Token::Semicolon,
+    ];
+    let printed = print_tokens(&tokens);
+    assert_eq!(scan(&printed), tokens);
+    assert_eq!(
+        print_tokens(&scan("var X : int := 4 + (6 * 2);\n print X;")),
+        "var X : int := 4 + (6 * 2);\nprint X;"
+    );
+
+    let tokens = scan(
+        r#" var nTimes : int := 0;
+ print "How many times?";
+ read nTimes;
+ for x in 0..nTimes-1 do
+  print " : Hello, World!\n";
+ end for;
+ assert (!(x = nTimes) | 1<=2);"#,
+    );
+    assert_eq!(scan(&print_tokens(&tokens)), tokens);
+}
+
+#[test]
+fn string_escapes() {
+    let (tokens, errors) = scan_with(
+        LexerConfig::default(),
+        r#""\u0041\u00e4x" "\U0001F600!" "\u{1F600}\u{41}" "\x7e!\x3bb" "\x41BC\xe4" "\101\0""#,
+    );
+    assert_eq!(
+        tokens,
+        vec![
+            string("Aäx"),
+            string("😀!"),
+            string("😀A"),
+            string("~!;b"),
+            string("ABCä"),
+            string("A\0"),
+        ]
+    );
+    assert!(errors.is_empty());
+
+    let (tokens, errors) = scan_with(
+        LexerConfig::default(),
+        r#""\8\u{}\u{12g}\u{110000}\uD800\u{123456789a}\u{41"x"#,
+    );
+    assert_eq!(tokens, vec![string(""), Token::Identifier("x".into())]);
+    assert_eq!(
+        errors,
+        vec![
+            error(LexErrorKind::UnsupportedEscape('8'), 1, 3, 1, 2),
+            error(LexErrorKind::EmptyUnicodeEscape, 3, 7, 1, 4),
+            error(LexErrorKind::InvalidUnicodeDigit('g'), 12, 13, 1, 13),
+            error(LexErrorKind::InvalidCodePoint(0x110000), 14, 24, 1, 15),
+            error(LexErrorKind::InvalidCodePoint(0xD800), 24, 30, 1, 25),
+            error(LexErrorKind::HexEscapeTooLong, 30, 44, 1, 31),
+            error(LexErrorKind::UnclosedUnicodeEscape, 44, 49, 1, 45),
+        ]
+    );
+}
+
+#[test]
+fn raw_and_multi_line_strings() {
+    let extended = LexerConfig::new(Dialect::Extended);
+
+    let source = r####"r"C:\path" r#"say "hi""# r##"a"#b"## r"" rx"####;
+    assert_eq!(
+        scan_error_kinds(extended.clone(), source),
+        (
+            vec![
+                string("C:\\path"),
+                string("say \"hi\""),
+                string("a\"#b"),
+                string(""),
+                Token::Identifier("rx".into()),
+            ],
+            vec![],
+        )
+    );
+    // Raw strings are an extension. In standard Mini-PL r is just an identifier.
+    assert_eq!(
+        scan_error_kinds(LexerConfig::default(), r#"r"x""#).0,
+        vec![Token::Identifier("r".into()), string("x")]
+    );
+    assert_eq!(
+        scan_error_kinds(extended.clone(), "r#x r#\"abc"),
+        (
+            vec![Token::Identifier("x".into())],
+            vec![
+                LexErrorKind::InvalidRawStringStart,
+                LexErrorKind::UnterminatedString,
+            ],
+        )
+    );
+
+    let source = "\"a\nb\" r\"c\nd\"";
+    assert_eq!(
+        scan_error_kinds(extended.clone(), source),
+        (vec![string("a\nb"), string("c\nd")], vec![])
+    );
+    let single_line = LexerConfig {
+        multiline_strings: false,
+        ..extended
+    };
+    assert_eq!(
+        scan_error_kinds(single_line.clone(), "\"ab\ncd;\n\"e\\\n"),
+        (
+            vec![
+                string("ab"),
+                Token::Identifier("cd".into()),
+                Token::Semicolon,
+                string("e"),
+            ],
+            vec![
+                LexErrorKind::NewlineInString,
+                LexErrorKind::DanglingEscape,
+                LexErrorKind::NewlineInString,
+            ],
+        )
+    );
+    assert_eq!(
+        scan_error_kinds(single_line, "r#\"a\n"),
+        (vec![string("a")], vec![LexErrorKind::NewlineInString])
+    );
+}
+
+#[test]
+fn unicode_identifiers_and_illegal_characters() {
+    let source = "var größe_2 : int;\nprint cafe\u{301} @ café#x;\n_x ³ \u{200B}";
+    let mut scanner = Scanner::new();
+    let mut tokens = Vec::new();
+    scanner.scan(source, &mut tokens);
+    scanner.finish(&mut tokens);
+    let tokens: Vec<Token> = tokens.into_iter().map(|t| t.token).collect();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::KeyWord(KeyWord::Var),
+            Token::Identifier("größe_2".into()),
+            Token::Colon,
+            Token::KeyWord(KeyWord::Int),
+            Token::Semicolon,
+            Token::KeyWord(KeyWord::Print),
+            // Both spellings of café are normalized into the same identifier.
+            Token::Identifier("café".into()),
+            Token::Identifier("café".into()),
+            Token::Identifier("x".into()),
+            Token::Semicolon,
+            Token::Identifier("x".into()),
+        ]
+    );
+    let illegal = |c: char, start, line, column| {
+        error(LexErrorKind::IllegalCharacter(c), start, start + c.len_utf8(), line, column)
+    };
+    assert_eq!(
+        scanner.errors(),
+        &[
+            illegal('@', 34, 2, 13),
+            illegal('#', 41, 2, 19),
+            illegal('_', 45, 3, 1),
+            illegal('³', 48, 3, 4),
+            illegal('\u{200B}', 51, 3, 6),
+        ][..]
+    );
+
+    let mut scanner = Scanner::with_config(LexerConfig {
+        normalize_identifiers: false,
+        ..LexerConfig::default()
+    });
+    let mut tokens = Vec::new();
+    scanner.scan("cafe\u{301} café", &mut tokens);
+    scanner.finish(&mut tokens);
+    assert_eq!(
+        tokens.into_iter().map(|t| t.token).collect::<Vec<_>>(),
+        vec![
+            Token::Identifier("cafe\u{301}".into()),
+            Token::Identifier("café".into()),
+        ]
+    );
+}
+
+#[test]
+fn doc_comments() {
+    let source = "/// A counter.\n//// not docs\n/** Block\n docs */ /**/ /***/ /*** no */ /** nested /* */ */\nx///last";
+    assert_eq!(
+        scan(source),
+        vec![
+            Token::DocComment(" A counter.".into()),
+            Token::DocComment(" Block\n docs ".into()),
+            Token::DocComment(" nested /* */ ".into()),
+            Token::Identifier("x".into()),
+            Token::DocComment("last".into()),
+        ]
+    );
+    let mut scanner = Scanner::with_trivia();
+    let mut tokens = Vec::new();
+    scanner.scan(source, &mut tokens);
+    scanner.finish(&mut tokens);
+    assert_eq!(reconstruct_source(&tokens), source);
+    assert_eq!(tokens[0].text, "/// A counter.");
+    assert_eq!(tokens[0].span.end, 14);
+
+    let tokens = scan(source);
+    assert_eq!(scan(&print_tokens(&tokens)), tokens);
 }
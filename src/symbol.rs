@@ -0,0 +1,87 @@
+//     mini-pl compiler.
+//     Copyright (C) 2018  Victor Bankowski
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// A handle to an interned string: the index of the string in the interner. Interning the same
+/// string always gives the same ´Symbol´, so symbols can be copied, compared and hashed without
+/// touching the string itself.
+/// The interner is global which lets the scanner, parser and interpreter threads share symbols.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+/// Owns the strings of the symbols. Each distinct string is stored once, so re-scanning the same
+/// source doesn't use more memory.
+struct Interner {
+    /// The strings indexed by their symbols.
+    strings: Vec<Arc<str>>,
+    symbols: HashMap<Arc<str>, Symbol>,
+}
+
+fn interner() -> &'static RwLock<Interner> {
+    static INTERNER: OnceLock<RwLock<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(|| {
+        RwLock::new(Interner {
+            strings: Vec::new(),
+            symbols: HashMap::new(),
+        })
+    })
+}
+
+impl Symbol {
+    /// Returns the symbol for ´string´, interning it if it hasn't been seen before.
+    pub fn intern(string: &str) -> Symbol {
+        if let Some(&symbol) = interner().read().unwrap().symbols.get(string) {
+            return symbol;
+        }
+        let mut interner = interner().write().unwrap();
+        // Another thread may have interned the string between the locks.
+        if let Some(&symbol) = interner.symbols.get(string) {
+            return symbol;
+        }
+        let index = u32::try_from(interner.strings.len()).expect("too many symbols");
+        let string: Arc<str> = Arc::from(string);
+        interner.strings.push(string.clone());
+        interner.symbols.insert(string, Symbol(index));
+        Symbol(index)
+    }
+
+    /// The string this symbol was interned from.
+    pub fn as_str(self) -> Arc<str> {
+        interner().read().unwrap().strings[self.0 as usize].clone()
+    }
+}
+
+impl<'a> From<&'a str> for Symbol {
+    fn from(string: &'a str) -> Self {
+        Symbol::intern(string)
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.as_str())
+    }
+}
@@ -1,4 +1,5 @@
 use std::collections::VecDeque;
+use std::fmt;
 use std::sync::mpsc::Sender;
 use std::sync::mpsc::Receiver;
 
@@ -8,6 +9,32 @@ pub enum Direction {
     Right,
 }
 
+/// A region of the source text. ´start´ and ´end´ are byte offsets (´end´ is exclusive) while
+/// ´line´ and ´column´ point at the first character of the region. Lines and columns start from 1.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    /// Creates a span from the start of this span to the end of ´end´.
+    pub fn to(self, end: Span) -> Span {
+        Span {
+            end: end.end,
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
 ///´Source´'s are sources for some type T. Taking from a source returns an optional.
 /// While a ´Source´ has things it should return Some(T).
 /// If the ´Source´ permanently runs out of things it should return None signaling to